@@ -0,0 +1,62 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[cfg(feature = "rayon")]
+use rusty_chromaprint::compare_fingerprints_parallel;
+use rusty_chromaprint::{
+    compare_fingerprints, compress_fingerprint, decompress_fingerprint, generate_fingerprint, CompareOptions,
+};
+
+/// Fingerprint of the test audio file.
+const REFERENCE: [u32; 43] = [
+    3086176501, 3077772469, 3077638581, 3052408789, 3048228821, 3046201301, 3042148311,
+    3037102035, 2969993073, 3041294129, 3045483313, 3046514967, 3050712326, 3040164098,
+    3040163847, 3073719559, 3073733965, 3212169693, 3212169693, 3220542455, 3220542399,
+    3212152503, 3077933717, 3086327509, 3080034295, 4120237047, 4119197543, 4119295527,
+    4123424293, 1975934501, 2110152245, 2111233559, 2144501255, 1005778439, 1001636359,
+    1005683463, 1005682948, 1005686104, 991003132, 991031785, 995223531, 995190635,
+    1003562858,
+];
+
+/// Number of subfingerprints in roughly 10 minutes of audio.
+const LARGE_LEN: usize = 5000;
+
+fn compression(c: &mut Criterion) {
+    let large = generate_fingerprint(1, LARGE_LEN);
+    c.bench_function("compress reference", |b| b.iter(|| compress_fingerprint(black_box(&REFERENCE), 1)));
+    c.bench_function("compress large", |b| b.iter(|| compress_fingerprint(black_box(&large), 1)));
+    // Every subfingerprint differs from its predecessor in all of its bits.
+    let dense = (0..LARGE_LEN).map(|i| if i % 2 == 0 { 0 } else { u32::MAX }).collect::<Vec<_>>();
+    c.bench_function("compress dense", |b| b.iter(|| compress_fingerprint(black_box(&dense), 1)));
+
+    let reference = compress_fingerprint(&REFERENCE, 1);
+    let large = compress_fingerprint(&large, 1);
+    c.bench_function("decompress reference", |b| b.iter(|| decompress_fingerprint(black_box(&reference))));
+    c.bench_function("decompress large", |b| b.iter(|| decompress_fingerprint(black_box(&large))));
+}
+
+fn comparison(c: &mut Criterion) {
+    let opts = CompareOptions::default();
+    c.bench_function("compare reference", |b| {
+        b.iter(|| compare_fingerprints(black_box(&REFERENCE), black_box(&REFERENCE[5..]), &opts))
+    });
+
+    let large = generate_fingerprint(1, LARGE_LEN);
+    let shifted = large[100..].to_vec();
+    c.bench_function("compare large", |b| {
+        b.iter(|| compare_fingerprints(black_box(&large), black_box(&shifted), &opts))
+    });
+
+    #[cfg(feature = "rayon")]
+    {
+        let opts = CompareOptions { max_offset: 2000, ..CompareOptions::default() };
+        c.bench_function("compare large serially", |b| {
+            b.iter(|| compare_fingerprints(black_box(&large), black_box(&shifted), &opts))
+        });
+        c.bench_function("compare large in parallel", |b| {
+            b.iter(|| compare_fingerprints_parallel(black_box(&large), black_box(&shifted), &opts))
+        });
+    }
+}
+
+criterion_group!(benches, compression, comparison);
+criterion_main!(benches);
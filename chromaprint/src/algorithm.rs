@@ -0,0 +1,87 @@
+use crate::fingerprinter::Configuration;
+
+/// Id of the algorithm used by [Configuration::preset_test1].
+pub const ALGORITHM_TEST1: u8 = 0;
+/// Id of the algorithm used by [Configuration::preset_test2].
+pub const ALGORITHM_TEST2: u8 = 1;
+/// Id of the algorithm used by [Configuration::preset_test3].
+pub const ALGORITHM_TEST3: u8 = 2;
+/// Id of the algorithm used by [Configuration::preset_test4].
+pub const ALGORITHM_TEST4: u8 = 3;
+/// Id of the algorithm used by [Configuration::preset_test5].
+pub const ALGORITHM_TEST5: u8 = 4;
+/// Id of the algorithm used by default in Chromaprint.
+pub const ALGORITHM_DEFAULT: u8 = ALGORITHM_TEST2;
+
+/// Name of the Chromaprint constant for the given algorithm id, for display and logging.
+///
+/// [ALGORITHM_TEST2] is reported as `CHROMAPRINT_ALGORITHM_DEFAULT`, since that is the id written
+/// by a default fpcalc. Unknown ids are reported as `UNKNOWN`.
+pub fn algorithm_name(algorithm: u8) -> &'static str {
+    match algorithm {
+        ALGORITHM_TEST1 => "CHROMAPRINT_ALGORITHM_TEST1",
+        ALGORITHM_DEFAULT => "CHROMAPRINT_ALGORITHM_DEFAULT",
+        ALGORITHM_TEST3 => "CHROMAPRINT_ALGORITHM_TEST3",
+        ALGORITHM_TEST4 => "CHROMAPRINT_ALGORITHM_TEST4",
+        ALGORITHM_TEST5 => "CHROMAPRINT_ALGORITHM_TEST5",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Duration (in seconds) of a single subfingerprint produced by the given algorithm.
+///
+/// Returns `None` for unknown algorithms.
+pub fn subfingerprint_duration(algorithm: u8) -> Option<f32> {
+    let config = match algorithm {
+        ALGORITHM_TEST1 => Configuration::preset_test1(),
+        // TEST4 differs from TEST2 only by removing silence.
+        ALGORITHM_TEST2 | ALGORITHM_TEST4 => Configuration::preset_test2(),
+        ALGORITHM_TEST3 => Configuration::preset_test3(),
+        ALGORITHM_TEST5 => Configuration::preset_test5(),
+        _ => return None,
+    };
+    Some(config.item_duration_in_seconds())
+}
+
+/// Duration (in seconds) of audio covered by `fp_len` subfingerprints of the given algorithm.
+///
+/// Returns `None` for unknown algorithms.
+pub fn fingerprint_duration(fp_len: usize, algorithm: u8) -> Option<f32> {
+    subfingerprint_duration(algorithm).map(|duration| duration * fp_len as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::{
+        algorithm_name, fingerprint_duration, subfingerprint_duration, ALGORITHM_DEFAULT, ALGORITHM_TEST5,
+    };
+    use crate::assert_eq_float;
+
+    #[test]
+    fn default_duration() {
+        assert_eq_float!(subfingerprint_duration(ALGORITHM_DEFAULT).unwrap(), 0.1238, 0.0001);
+        assert_eq_float!(fingerprint_duration(1000, ALGORITHM_DEFAULT).unwrap(), 123.8, 0.1);
+    }
+
+    #[test]
+    fn unknown_algorithm() {
+        assert!(subfingerprint_duration(ALGORITHM_TEST5).is_some());
+        assert_eq!(subfingerprint_duration(ALGORITHM_TEST5 + 1), None);
+        assert_eq!(fingerprint_duration(10, 255), None);
+    }
+
+    #[test]
+    fn names() {
+        let names: Vec<_> = (0..=4).map(algorithm_name).collect();
+        assert_eq!(names, [
+            "CHROMAPRINT_ALGORITHM_TEST1",
+            "CHROMAPRINT_ALGORITHM_DEFAULT",
+            "CHROMAPRINT_ALGORITHM_TEST3",
+            "CHROMAPRINT_ALGORITHM_TEST4",
+            "CHROMAPRINT_ALGORITHM_TEST5",
+        ]);
+        assert_eq!(algorithm_name(ALGORITHM_DEFAULT), "CHROMAPRINT_ALGORITHM_DEFAULT");
+        assert_eq!(algorithm_name(5), "UNKNOWN");
+        assert_eq!(algorithm_name(255), "UNKNOWN");
+    }
+}
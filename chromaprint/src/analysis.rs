@@ -0,0 +1,202 @@
+use crate::compression::xor_deltas;
+
+/// Estimates how much a fingerprint changes from one item to the next.
+///
+/// Returns the average fraction of bits that differ between consecutive subfingerprints,
+/// so near-silent or looping audio scores close to 0, while noise scores around 0.5.
+pub fn fingerprint_entropy(fp: &[u32]) -> f32 {
+    if fp.len() < 2 {
+        return 0.0;
+    }
+
+    let changed_bits: u64 = xor_deltas(fp.iter().copied())
+        .skip(1)
+        .map(|delta| u64::from(delta.count_ones()))
+        .sum();
+    changed_bits as f32 / ((fp.len() - 1) * 32) as f32
+}
+
+/// Yields the average number of bits that change between consecutive subfingerprints
+/// over every window of `window` consecutive changes.
+///
+/// Windows slide by one item, so this yields `fp.len() - window` values (none if the
+/// fingerprint is too short or `window` is 0). Dips indicate repeated or silent parts.
+pub fn change_rate_windows(fp: &[u32], window: usize) -> impl Iterator<Item = f32> + '_ {
+    let changes = move |i: usize| (fp[i] ^ fp[i - 1]).count_ones();
+    let count = if window == 0 { 0 } else { fp.len().saturating_sub(window) };
+
+    let mut sum = 0;
+    (0..count).map(move |start| {
+        if start == 0 {
+            sum = (1..=window).map(changes).sum();
+        } else {
+            sum = sum + changes(start + window) - changes(start);
+        }
+        sum as f32 / window as f32
+    })
+}
+
+/// Removes a trailing run of identical subfingerprints, keeping its first item.
+///
+/// Silence or a fade-out at the end of a track produces the same subfingerprint over and over,
+/// which would otherwise dominate matching. Only the very end is trimmed: a fingerprint
+/// whose last two items differ is returned unchanged, and repeated items elsewhere are kept.
+pub fn trim_constant_tail(fp: &[u32]) -> &[u32] {
+    let Some(&last) = fp.last() else {
+        return fp;
+    };
+    let run_start = fp.iter().rposition(|&sub_fp| sub_fp != last).map_or(0, |i| i + 1);
+    &fp[..=run_start]
+}
+
+/// Truncates or pads a fingerprint with `pad` to exactly `target` subfingerprints,
+/// e.g. to build feature vectors of a fixed size.
+///
+/// Padding does not correspond to any audio, so resized fingerprints should only be compared
+/// with each other, not with fingerprints of the original length.
+pub fn resize_fingerprint(fp: &[u32], target: usize, pad: u32) -> Vec<u32> {
+    let mut resized = fp[..target.min(fp.len())].to_vec();
+    resized.resize(target, pad);
+    resized
+}
+
+/// Keeps only the highest `keep_high_bits` bits of every subfingerprint, zeroing the rest.
+///
+/// This is lossy: subfingerprints differing only in the dropped bits become equal, so quantized
+/// fingerprints are only useful as a coarse pre-filter before comparing the original ones.
+/// Values of `keep_high_bits` above 32 keep all the bits.
+pub fn quantize(fp: &[u32], keep_high_bits: u32) -> Vec<u32> {
+    let mask = u32::MAX.checked_shl(32 - keep_high_bits.min(32)).unwrap_or(0);
+    fp.iter().map(|sub_fp| sub_fp & mask).collect()
+}
+
+/// Folds every subfingerprint into 16 bits by XORing its upper and lower halves.
+///
+/// Equal subfingerprints always fold into equal values, so the folded ones can be used
+/// as cheap keys for finding candidate matches. Different subfingerprints collide whenever
+/// their halves XOR to the same value, e.g. when both halves are swapped, so candidates
+/// have to be confirmed by comparing the original fingerprints.
+pub fn mini_fingerprints(fp: &[u32]) -> Vec<u16> {
+    fp.iter().map(|&sub_fp| ((sub_fp >> 16) ^ sub_fp) as u16).collect()
+}
+
+/// Reverses the order of bits in every subfingerprint, for comparing with tools
+/// that number the bits from the other end.
+pub fn reverse_bits_u32(fp: &[u32]) -> Vec<u32> {
+    fp.iter().map(|sub_fp| sub_fp.reverse_bits()).collect()
+}
+
+/// Formats a subfingerprint as 32 binary digits.
+///
+/// With `msb_first` the lowest bit is on the right, like in the examples of the compression
+/// format, otherwise it is on the left.
+pub fn format_subfingerprint(v: u32, msb_first: bool) -> String {
+    let v = if msb_first { v } else { v.reverse_bits() };
+    format!("{v:032b}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{
+        change_rate_windows, fingerprint_entropy, format_subfingerprint, mini_fingerprints, quantize,
+        resize_fingerprint, reverse_bits_u32, trim_constant_tail,
+    };
+    use crate::assert_eq_float;
+    use crate::utils::pseudo_random;
+
+    #[test]
+    fn entropy_of_constant_fingerprint() {
+        assert_eq_float!(fingerprint_entropy(&[0xdeadbeef; 100]), 0.0);
+        assert_eq_float!(fingerprint_entropy(&[0xdeadbeef]), 0.0);
+        assert_eq_float!(fingerprint_entropy(&[]), 0.0);
+    }
+
+    #[test]
+    fn entropy_of_random_fingerprint() {
+        assert_eq_float!(fingerprint_entropy(&pseudo_random(1, 1000)), 0.5, 0.02);
+    }
+
+    #[test]
+    fn change_rate_of_constant_fingerprint() {
+        let rates = change_rate_windows(&[0xdeadbeef; 100], 10).collect::<Vec<_>>();
+        assert_eq!(rates.len(), 90);
+        assert!(rates.iter().all(|&rate| rate == 0.0));
+    }
+
+    #[test]
+    fn change_rate_of_random_fingerprint() {
+        let fp = pseudo_random(1, 1000);
+        let rates = change_rate_windows(&fp, 100).collect::<Vec<_>>();
+        assert_eq!(rates.len(), 900);
+        assert!(rates.iter().all(|&rate| (rate - 16.0).abs() < 1.5));
+
+        let expected = (901..=1000).map(|i| (fp[i - 1] ^ fp[i - 2]).count_ones()).sum::<u32>() as f32 / 100.0;
+        assert_eq_float!(rates[899], expected);
+    }
+
+    #[test]
+    fn change_rate_of_short_fingerprint() {
+        assert_eq!(change_rate_windows(&[1, 2, 3], 3).count(), 0);
+        assert_eq!(change_rate_windows(&[1, 2, 3], 2).count(), 1);
+        assert_eq!(change_rate_windows(&[1, 2, 3], 0).count(), 0);
+    }
+
+    #[test]
+    fn constant_tail() {
+        // Subfingerprint of silence, as calculated by Chromaprint.
+        const SILENCE: u32 = 627964279;
+
+        let mut fp = pseudo_random(1, 100);
+        let track = fp.clone();
+        fp.extend([SILENCE; 50]);
+        assert_eq!(trim_constant_tail(&fp), [&track[..], &[SILENCE]].concat());
+        assert_eq!(trim_constant_tail(&track), track);
+
+        assert_eq!(trim_constant_tail(&[1, 1, 2]), [1, 1, 2]);
+        assert_eq!(trim_constant_tail(&[SILENCE; 10]), [SILENCE]);
+        assert!(trim_constant_tail(&[]).is_empty());
+    }
+
+    #[test]
+    fn bit_order() {
+        assert_eq!(format_subfingerprint(0b0100_0110, true), "00000000000000000000000001000110");
+        assert_eq!(format_subfingerprint(0b0100_0110, false), "01100010000000000000000000000000");
+        assert_eq!(format_subfingerprint(1 << 31, false), format_subfingerprint(1, true));
+
+        assert_eq!(reverse_bits_u32(&[1, 0b0100_0110, u32::MAX]), [1 << 31, 0x6200_0000, u32::MAX]);
+        assert!(reverse_bits_u32(&[]).is_empty());
+    }
+
+    #[test]
+    fn resizing() {
+        let fp = pseudo_random(1, 100);
+        assert_eq!(resize_fingerprint(&fp, 60, 0), fp[..60]);
+        assert_eq!(resize_fingerprint(&fp, 100, 0), fp);
+        assert_eq!(resize_fingerprint(&fp, 120, 7), [&fp[..], &[7; 20]].concat());
+        assert_eq!(resize_fingerprint(&[], 3, 0), [0; 3]);
+        assert!(resize_fingerprint(&fp, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn quantization() {
+        let fp = pseudo_random(1, 100);
+        assert_eq!(quantize(&fp, 32), fp);
+        assert_eq!(quantize(&fp, 33), fp);
+        assert_eq!(quantize(&fp, 0), [0; 100]);
+
+        assert_eq!(quantize(&[0xdead_beef, 0x1234_5678], 8), [0xde00_0000, 0x1200_0000]);
+        assert_eq!(quantize(&[u32::MAX], 1), [1 << 31]);
+    }
+
+    #[test]
+    fn folding() {
+        let fp = [0x1234_5678, 0xdead_beef, 0x1234_5678, 0x5678_1234];
+        let minis = mini_fingerprints(&fp);
+        assert_eq!(minis, [0x444c, 0x6042, 0x444c, 0x444c]);
+        assert_eq!(minis[0], minis[2]);
+
+        let fp = pseudo_random(1, 100);
+        assert_eq!(mini_fingerprints(&fp).len(), fp.len());
+        assert!(mini_fingerprints(&[]).is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+//! Encoding of compressed fingerprints as text, like `fpcalc` prints them.
+//!
+//! This is base64 with the URL-safe alphabet and without padding. Decoding skips
+//! ASCII whitespace and a leading byte order mark, which often sneak in when
+//! fingerprints are copied from JSON or logs.
+
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Write};
+
+use crate::compression::{
+    compress_fingerprint, compress_fingerprint_with, decompress_fingerprint, decompress_stream, max_length,
+    DecompressError,
+};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Text has a length that base64 cannot have, `index` is the byte index of its last character.
+    InvalidBase64 { index: usize },
+    /// Text contains a character outside of the alphabet, `index` is its byte index in the text
+    /// and `byte` is its first byte.
+    InvalidChar { index: usize, byte: u8 },
+    /// Decoded bytes are not a valid compressed fingerprint.
+    Decompress(DecompressError),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidBase64 { index } => write!(f, "Invalid base64 length at index {index}"),
+            DecodeError::InvalidChar { index, byte } => write!(f, "Invalid base64 byte {byte:#04x} at index {index}"),
+            DecodeError::Decompress(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::InvalidBase64 { .. } | DecodeError::InvalidChar { .. } => None,
+            DecodeError::Decompress(err) => Some(err),
+        }
+    }
+}
+
+impl From<DecompressError> for DecodeError {
+    fn from(err: DecompressError) -> Self {
+        DecodeError::Decompress(err)
+    }
+}
+
+/// Compresses a fingerprint and encodes it as base64.
+#[must_use]
+pub fn encode_fingerprint(fp: &[u32], algorithm: u8) -> String {
+    encode_bytes(&compress_fingerprint(fp, algorithm))
+}
+
+/// Compresses a fingerprint and writes it as base64 to `out`, without allocating.
+///
+/// Compressed bytes are encoded as they are produced, so neither the compressed fingerprint
+/// nor the text is built in memory. This fits fixed-size buffers and formatting machinery,
+/// e.g. `write!` in a [Display] implementation. Errors of `out` are passed on, in which case
+/// a part of the text might have been written.
+pub fn encode_fingerprint_into<W: Write>(fp: &[u32], algorithm: u8, out: &mut W) -> std::fmt::Result {
+    let mut encoder = Encoder::new(out);
+    compress_fingerprint_with(fp, algorithm, |byte| encoder.push(byte));
+    encoder.finish()
+}
+
+/// Decodes a base64-encoded fingerprint, returning the id of its algorithm and its subfingerprints.
+pub fn decode_fingerprint(encoded: &str) -> Result<(u8, Vec<u32>), DecodeError> {
+    Ok(decompress_fingerprint(&decode_bytes(encoded)?)?)
+}
+
+/// Decodes a base64-encoded fingerprint like [decode_fingerprint], but without decoding
+/// the whole compressed fingerprint into memory first.
+///
+/// Base64 is decoded on the fly, so apart from the output only a few bytes are buffered.
+/// Only text containing whitespace is copied, to strip it.
+pub fn decode_fingerprint_streaming(encoded: &str) -> Result<(u8, Vec<u32>), DecodeError> {
+    let encoded = validate(encoded)?;
+    let encoded = &encoded[..];
+
+    // The length in the header is only used to size the output and is checked while decoding.
+    let size = encoded.len() * 3 / 4;
+    let length = decoded_bytes(encoded, 1).take(3).fold(0, |length, byte| (length << 8) | usize::from(byte));
+    let mut fp = Vec::with_capacity(usize::min(length, max_length(size)));
+    let algorithm = decompress_stream(size, |offset| decoded_bytes(encoded, offset), |sub_fp| fp.push(sub_fp))?;
+    Ok((algorithm, fp))
+}
+
+/// Encodes arbitrary bytes as base64.
+pub(crate) fn encode_bytes(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    write_bytes(bytes, &mut encoded).expect("writing to a String never fails");
+    encoded
+}
+
+/// Writes arbitrary bytes as base64 to `out`.
+fn write_bytes<W: Write>(bytes: &[u8], out: &mut W) -> std::fmt::Result {
+    let mut encoder = Encoder::new(out);
+    bytes.iter().for_each(|&byte| encoder.push(byte));
+    encoder.finish()
+}
+
+/// Encodes bytes as base64 as they come, writing every complete group of 3 bytes to `out`.
+///
+/// The first error of `out` is kept and nothing is written after it.
+struct Encoder<'a, W: Write> {
+    out: &'a mut W,
+    group: u32,
+    len: usize,
+    result: std::fmt::Result,
+}
+
+impl<'a, W: Write> Encoder<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        Self { out, group: 0, len: 0, result: Ok(()) }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.group |= u32::from(byte) << (16 - 8 * self.len);
+        self.len += 1;
+        if self.len == 3 {
+            self.write_group();
+        }
+    }
+
+    /// Writes the last incomplete group, if any, and returns the first error of `out`.
+    fn finish(mut self) -> std::fmt::Result {
+        if self.len > 0 {
+            self.write_group();
+        }
+        self.result
+    }
+
+    /// Writes the characters of the buffered bytes, which are one more than the bytes.
+    fn write_group(&mut self) {
+        for i in 0..=self.len {
+            if self.result.is_ok() {
+                let value = (self.group >> (18 - 6 * i)) & 0b11_1111;
+                self.result = self.out.write_char(ALPHABET[value as usize] as char);
+            }
+        }
+        self.group = 0;
+        self.len = 0;
+    }
+}
+
+/// Decodes base64 into bytes, without interpreting them.
+pub(crate) fn decode_bytes(encoded: &str) -> Result<Vec<u8>, DecodeError> {
+    let encoded = validate(encoded)?;
+    Ok(decoded_bytes(&encoded, 0).collect())
+}
+
+/// Checks that all the characters belong to the alphabet and that the length is possible.
+///
+/// Returns the characters without the byte order mark and whitespace.
+fn validate(encoded: &str) -> Result<Cow<'_, [u8]>, DecodeError> {
+    let bom_size = if encoded.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let text = &encoded.as_bytes()[bom_size..];
+    if let Some(index) = text.iter().position(|&c| value(c).is_none() && !c.is_ascii_whitespace()) {
+        return Err(DecodeError::InvalidChar { index: bom_size + index, byte: text[index] });
+    }
+
+    let encoded = if text.iter().any(u8::is_ascii_whitespace) {
+        Cow::Owned(text.iter().copied().filter(|c| !c.is_ascii_whitespace()).collect())
+    } else {
+        Cow::Borrowed(text)
+    };
+    // A single character in the last group does not encode even one full byte.
+    if encoded.len() % 4 == 1 {
+        let index = text.iter().rposition(|c| !c.is_ascii_whitespace()).unwrap_or(0);
+        return Err(DecodeError::InvalidBase64 { index: bom_size + index });
+    }
+    Ok(encoded)
+}
+
+/// Decodes validated base64, starting from the decoded byte at `offset`.
+fn decoded_bytes(encoded: &[u8], offset: usize) -> impl Iterator<Item = u8> + '_ {
+    let start = usize::min(offset / 3 * 4, encoded.len());
+    encoded[start..]
+        .chunks(4)
+        .flat_map(|chunk| {
+            let group = chunk.iter().enumerate().fold(0u32, |group, (i, &c)| {
+                group | (u32::from(value(c).unwrap_or(0)) << (18 - 6 * i))
+            });
+            let bytes = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+            bytes.into_iter().take(chunk.len() - 1)
+        })
+        .skip(offset % 3)
+}
+
+fn value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'-' => Some(62),
+        b'_' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::{
+        decode_fingerprint, decode_fingerprint_streaming, encode_fingerprint, encode_fingerprint_into, DecodeError,
+    };
+    use crate::compression::DecompressError;
+    use crate::utils::{count_allocations, pseudo_random};
+
+    #[test]
+    fn known_encodings() {
+        // Compressed as "\x01\0\0\x01\x01".
+        assert_eq!(encode_fingerprint(&[1], 1), "AQAAAQE");
+        assert_eq!(encode_fingerprint(&[], 1), "AQAAAA");
+        assert_eq!(decode_fingerprint("AQAAAQE"), Ok((1, vec![1])));
+        assert_eq!(decode_fingerprint("AQAAAA"), Ok((1, vec![])));
+    }
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 2, 3, 100, 1000] {
+            let fp = pseudo_random(1, len);
+            let encoded = encode_fingerprint(&fp, 1);
+            assert_eq!(decode_fingerprint(&encoded), Ok((1, fp.clone())));
+            assert_eq!(decode_fingerprint_streaming(&encoded), Ok((1, fp)));
+        }
+    }
+
+    #[test]
+    fn encoding_into_writer() {
+        /// Buffer of a fixed capacity, failing when it is full.
+        struct FixedBuffer {
+            bytes: [u8; 128],
+            len: usize,
+        }
+
+        impl std::fmt::Write for FixedBuffer {
+            fn write_str(&mut self, s: &str) -> std::fmt::Result {
+                let end = self.len + s.len();
+                self.bytes.get_mut(self.len..end).ok_or(std::fmt::Error)?.copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+        }
+
+        let fp = pseudo_random(1, 5);
+        let mut text = String::from("fingerprint=");
+        encode_fingerprint_into(&fp, 1, &mut text).unwrap();
+        assert_eq!(text, format!("fingerprint={}", encode_fingerprint(&fp, 1)));
+
+        let mut buffer = FixedBuffer { bytes: [0; 128], len: 0 };
+        let (result, allocations) = count_allocations(|| encode_fingerprint_into(&fp, 1, &mut buffer));
+        assert_eq!(result, Ok(()));
+        assert_eq!(allocations.count, 0);
+        assert_eq!(&buffer.bytes[..buffer.len], encode_fingerprint(&fp, 1).as_bytes());
+
+        // The spans and extensions of these end at different positions within groups of 3 bytes.
+        for len in [0, 1, 2, 3, 10, 1000] {
+            let fp = pseudo_random(2, len);
+            let mut text = String::new();
+            encode_fingerprint_into(&fp, 1, &mut text).unwrap();
+            assert_eq!(text, encode_fingerprint(&fp, 1));
+        }
+
+        let mut buffer = FixedBuffer { bytes: [0; 128], len: 0 };
+        assert_eq!(encode_fingerprint_into(&pseudo_random(1, 100), 1, &mut buffer), Err(std::fmt::Error));
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(decode_fingerprint("AQA+AQE"), Err(DecodeError::InvalidChar { index: 3, byte: b'+' }));
+        assert_eq!(decode_fingerprint("AQAAA"), Err(DecodeError::InvalidBase64 { index: 4 }));
+        assert_eq!(DecodeError::InvalidBase64 { index: 4 }.to_string(), "Invalid base64 length at index 4");
+        assert_eq!(DecodeError::InvalidChar { index: 3, byte: b'+' }.to_string(), "Invalid base64 byte 0x2b at index 3");
+        assert_eq!(decode_fingerprint("AQAAAQ"), Err(DecodeError::Decompress(DecompressError::UnexpectedEndOfData)));
+
+        let encoded = encode_fingerprint(&pseudo_random(1, 100), 1);
+        for len in 0..encoded.len() {
+            assert!(decode_fingerprint_streaming(&encoded[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn whitespace() {
+        let fp = pseudo_random(1, 100);
+        let encoded = encode_fingerprint(&fp, 1);
+        let wrapped = encoded.as_bytes().chunks(20).map(|line| std::str::from_utf8(line).unwrap()).collect::<Vec<_>>();
+        let wrapped = format!("\u{feff} {}\r\n", wrapped.join("\n\t"));
+        assert_eq!(decode_fingerprint(&wrapped), Ok((1, fp.clone())));
+        assert_eq!(decode_fingerprint_streaming(&wrapped), Ok((1, fp)));
+
+        assert_eq!(decode_fingerprint("AQAA\nAQE*"), Err(DecodeError::InvalidChar { index: 8, byte: b'*' }));
+        assert_eq!(decode_fingerprint("\u{feff}AQ*"), Err(DecodeError::InvalidChar { index: 5, byte: b'*' }));
+        assert_eq!(decode_fingerprint("AQAA A\n"), Err(DecodeError::InvalidBase64 { index: 5 }));
+        // A byte order mark is only skipped at the start.
+        assert_eq!(decode_fingerprint("AQ\u{feff}"), Err(DecodeError::InvalidChar { index: 2, byte: 0xef }));
+    }
+
+    #[test]
+    fn implausible_length() {
+        // Header declaring 0xFFFFFF subfingerprints without any data.
+        let (decoded, allocations) = count_allocations(|| decode_fingerprint_streaming("Af___w"));
+        assert_eq!(decoded, Err(DecodeError::Decompress(DecompressError::UnexpectedEndOfData)));
+        assert!(allocations.bytes < 1024, "{allocations:?}");
+        assert_eq!(decode_fingerprint("Af___w"), decoded);
+    }
+
+    #[test]
+    fn streaming_allocates_only_output() {
+        let fp = pseudo_random(1, 100_000);
+        let encoded = encode_fingerprint(&fp, 1);
+
+        let (decoded, allocations) = count_allocations(|| decode_fingerprint(&encoded));
+        assert_eq!(decoded, Ok((1, fp.clone())));
+        let (streamed, streamed_allocations) = count_allocations(|| decode_fingerprint_streaming(&encoded));
+        assert_eq!(streamed, Ok((1, fp)));
+
+        assert_eq!(streamed_allocations.count, 1);
+        assert_eq!(streamed_allocations.bytes, 400_000);
+        assert!(allocations.bytes > streamed_allocations.bytes + encoded.len() / 2);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "ndarray")]
+use crate::compression::DecompressError;
+
+/// Size of the length preceding the planes in [BitPlanes].
+const LENGTH_SIZE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitPlanesError {
+    /// Data is shorter than the planes of the declared number of subfingerprints.
+    UnexpectedEndOfData,
+    /// Data continues after the planes of the declared number of subfingerprints.
+    TrailingData,
+}
+
+impl Display for BitPlanesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitPlanesError::UnexpectedEndOfData => write!(f, "Unexpected end of bit planes"),
+            BitPlanesError::TrailingData => write!(f, "Unexpected data after the end of bit planes"),
+        }
+    }
+}
+
+impl std::error::Error for BitPlanesError {}
+
+/// Transposes a fingerprint into 32 bit planes, one for every bit position.
+///
+/// Bit `j` of word `w` in plane `p` holds bit `p` of the subfingerprint `fp[w * 64 + j]`.
+/// The last word of each plane is padded with zeros.
+pub fn to_bit_planes(fp: &[u32]) -> [Vec<u64>; 32] {
+    let words = fp.len().div_ceil(64);
+    let mut planes: [Vec<u64>; 32] = std::array::from_fn(|_| vec![0; words]);
+    for (i, &sub_fp) in fp.iter().enumerate() {
+        let (word, bit) = (i / 64, i % 64);
+        for (p, plane) in planes.iter_mut().enumerate() {
+            plane[word] |= u64::from((sub_fp >> p) & 1) << bit;
+        }
+    }
+    planes
+}
+
+/// Reconstructs `len` subfingerprints from bit planes created by [to_bit_planes].
+pub fn from_bit_planes(planes: &[Vec<u64>; 32], len: usize) -> Vec<u32> {
+    (0..len)
+        .map(|i| {
+            let (word, bit) = (i / 64, i % 64);
+            planes.iter()
+                .enumerate()
+                .fold(0, |sub_fp, (p, plane)| sub_fp | (((plane[word] >> bit) & 1) as u32) << p)
+        })
+        .collect()
+}
+
+/// Counts differing bits between two fingerprints transposed into bit planes.
+///
+/// Both fingerprints are expected to have the same length.
+pub fn bit_planes_distance(a: &[Vec<u64>; 32], b: &[Vec<u64>; 32]) -> u64 {
+    a.iter()
+        .zip(b)
+        .flat_map(|(a, b)| a.iter().zip(b))
+        .map(|(a, b)| u64::from((a ^ b).count_ones()))
+        .sum()
+}
+
+/// Counts how many subfingerprints have each of the bits set.
+///
+/// This is a cheap signature of a fingerprint, which can be used to skip
+/// comparing fingerprints that are obviously different.
+pub fn bit_frequency(fp: &[u32]) -> [u32; 32] {
+    to_bit_planes(fp).map(|plane| plane.iter().map(|word| word.count_ones()).sum())
+}
+
+/// Fingerprint transposed into bit planes and stored as bytes, for example in a memory-mapped file.
+///
+/// The layout is the number of subfingerprints as a little-endian `u32`, followed by
+/// the 32 planes of [to_bit_planes], each of them as `len.div_ceil(64)` little-endian `u64` words.
+/// The bytes are borrowed and read in place, so they do not have to be aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitPlanes<'a> {
+    len: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> BitPlanes<'a> {
+    /// Reads bit planes written by [write_bit_planes], checking that their size matches the length.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, BitPlanesError> {
+        let length = bytes.get(..LENGTH_SIZE).ok_or(BitPlanesError::UnexpectedEndOfData)?;
+        let len = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        // A size that overflows cannot be the size of any slice.
+        let size = planes_size(len).ok_or(BitPlanesError::UnexpectedEndOfData)?;
+        match bytes.len() {
+            n if n < size => Err(BitPlanesError::UnexpectedEndOfData),
+            n if n > size => Err(BitPlanesError::TrailingData),
+            _ => Ok(Self { len, bytes: &bytes[LENGTH_SIZE..] }),
+        }
+    }
+
+    /// Number of the stored subfingerprints.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no stored subfingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Words of the plane for the bit position `p`, which must be less than 32.
+    pub fn plane(&self, p: usize) -> impl Iterator<Item = u64> + 'a {
+        let words = self.len.div_ceil(64);
+        self.bytes[p * words * 8..(p + 1) * words * 8]
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(std::array::from_fn(|i| word[i])))
+    }
+}
+
+/// Transposes a fingerprint into bit planes in the layout read by [BitPlanes].
+pub fn write_bit_planes(fp: &[u32]) -> Vec<u8> {
+    let len = u32::try_from(fp.len()).expect("fingerprint is too long");
+    let planes = to_bit_planes(fp);
+    let mut bytes = Vec::with_capacity(planes_size(fp.len()).expect("fingerprint is too long"));
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend(planes.iter().flatten().flat_map(|word| word.to_le_bytes()));
+    bytes
+}
+
+/// Size of the bit planes of `len` subfingerprints with their length, unless it overflows `usize`.
+fn planes_size(len: usize) -> Option<usize> {
+    len.div_ceil(64).checked_mul(32 * 8)?.checked_add(LENGTH_SIZE)
+}
+
+/// Counts differing bits between a fingerprint and stored bit planes, without reconstructing
+/// the stored fingerprint.
+///
+/// Fingerprints of different lengths are compared over their common prefix.
+pub fn compare_against_planes(query: &[u32], planes: &BitPlanes) -> u64 {
+    let len = usize::min(query.len(), planes.len());
+    let (full_words, rest) = (len / 64, len % 64);
+    let last_mask = (1u64 << rest) - 1;
+    to_bit_planes(&query[..len])
+        .iter()
+        .enumerate()
+        .flat_map(|(p, query)| query.iter().zip(planes.plane(p)).enumerate())
+        .map(|(w, (query, stored))| {
+            let diff = if w < full_words { query ^ stored } else { (query ^ stored) & last_mask };
+            u64::from(diff.count_ones())
+        })
+        .sum()
+}
+
+/// Decompresses a fingerprint into a matrix with a row of 32 bits per subfingerprint.
+///
+/// Column `p` of row `i` holds bit `p` of the subfingerprint `i` as 0 or 1,
+/// so the matrix can be plotted or fed to models directly.
+#[cfg(feature = "ndarray")]
+pub fn decompress_to_bits(compressed: &[u8]) -> Result<ndarray::Array2<u8>, DecompressError> {
+    let (_, fp) = crate::compression::decompress_fingerprint(compressed)?;
+    Ok(ndarray::Array2::from_shape_fn((fp.len(), 32), |(i, p)| ((fp[i] >> p) & 1) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bit_planes::{
+        bit_frequency, bit_planes_distance, compare_against_planes, from_bit_planes, to_bit_planes, write_bit_planes,
+        planes_size, BitPlanes, BitPlanesError,
+    };
+    #[cfg(feature = "ndarray")]
+    use crate::compression::DecompressError;
+    use crate::utils::pseudo_random;
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 63, 64, 65, 200] {
+            let fp = pseudo_random(1, len);
+            assert_eq!(from_bit_planes(&to_bit_planes(&fp), len), fp);
+        }
+    }
+
+    #[test]
+    fn distance_matches_scalar() {
+        let a = pseudo_random(1, 150);
+        let b = pseudo_random(2, 150);
+
+        let scalar: u64 = a.iter().zip(&b).map(|(a, b)| u64::from((a ^ b).count_ones())).sum();
+        assert_eq!(bit_planes_distance(&to_bit_planes(&a), &to_bit_planes(&b)), scalar);
+    }
+
+    #[test]
+    fn frequency() {
+        assert_eq!(bit_frequency(&[u32::MAX; 100]), [100; 32]);
+        assert_eq!(bit_frequency(&[0; 100]), [0; 32]);
+        assert_eq!(bit_frequency(&[]), [0; 32]);
+
+        let mut expected = [0; 32];
+        expected[0] = 2;
+        expected[31] = 1;
+        assert_eq!(bit_frequency(&[1, 1 << 31 | 1, 0]), expected);
+    }
+
+    #[test]
+    fn stored_planes() {
+        for len in [0, 1, 64, 150] {
+            let a = pseudo_random(1, len);
+            let b = pseudo_random(2, len);
+            let bytes = write_bit_planes(&b);
+            let planes = BitPlanes::from_bytes(&bytes).unwrap();
+            assert_eq!(planes.len(), len);
+            assert_eq!(planes.plane(31).collect::<Vec<_>>(), to_bit_planes(&b)[31]);
+
+            let scalar: u64 = a.iter().zip(&b).map(|(a, b)| u64::from((a ^ b).count_ones())).sum();
+            assert_eq!(compare_against_planes(&a, &planes), scalar);
+            assert_eq!(compare_against_planes(&b, &planes), 0);
+        }
+    }
+
+    #[test]
+    fn stored_planes_of_different_length() {
+        let a = pseudo_random(1, 100);
+        let bytes = write_bit_planes(&a);
+        let planes = BitPlanes::from_bytes(&bytes).unwrap();
+        assert_eq!(compare_against_planes(&a[..70], &planes), 0);
+
+        let mut longer = a.clone();
+        longer.push(u32::MAX);
+        assert_eq!(compare_against_planes(&longer, &planes), 0);
+    }
+
+    #[test]
+    fn unaligned_planes() {
+        let b = pseudo_random(2, 70);
+        let mut bytes = vec![0];
+        bytes.extend(write_bit_planes(&b));
+        let planes = BitPlanes::from_bytes(&bytes[1..]).unwrap();
+        assert_eq!(compare_against_planes(&b, &planes), 0);
+    }
+
+    #[test]
+    fn invalid_planes() {
+        let bytes = write_bit_planes(&pseudo_random(1, 10));
+        assert_eq!(BitPlanes::from_bytes(&bytes[..3]), Err(BitPlanesError::UnexpectedEndOfData));
+        assert_eq!(BitPlanes::from_bytes(&bytes[..bytes.len() - 1]), Err(BitPlanesError::UnexpectedEndOfData));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(BitPlanes::from_bytes(&trailing), Err(BitPlanesError::TrailingData));
+
+        // The largest declared length, whose planes do not fit in the address space of 32-bit targets.
+        assert_eq!(BitPlanes::from_bytes(&[0xFF; 64]), Err(BitPlanesError::UnexpectedEndOfData));
+        assert_eq!(planes_size(usize::MAX), None);
+        assert_eq!(planes_size(usize::MAX / 4), None);
+        assert_eq!(planes_size(70), Some(4 + 2 * 256));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn bit_matrix() {
+        use crate::bit_planes::decompress_to_bits;
+        use crate::compression::compress_fingerprint;
+
+        let fp = pseudo_random(1, 100);
+        let bits = decompress_to_bits(&compress_fingerprint(&fp, 1)).unwrap();
+        assert_eq!(bits.dim(), (100, 32));
+        let first = (0..32).map(|p| ((fp[0] >> p) & 1) as u8).collect::<Vec<_>>();
+        assert_eq!(bits.row(0).to_vec(), first);
+        let set_bits = fp.iter().map(|sub_fp| sub_fp.count_ones() as usize).sum::<usize>();
+        assert_eq!(bits.iter().map(|&bit| usize::from(bit)).sum::<usize>(), set_bits);
+
+        assert_eq!(decompress_to_bits(&compress_fingerprint(&[], 1)).unwrap().dim(), (0, 32));
+        assert_eq!(decompress_to_bits(&[1, 0]), Err(DecompressError::UnexpectedEndOfData));
+    }
+}
@@ -51,9 +51,8 @@ impl<C: FeatureVectorConsumer> Stage for Chroma<C> {
 impl<C: FeatureVectorConsumer> FeatureVectorConsumer for Chroma<C> {
     fn consume(&mut self, frame: &[f64]) {
         self.features.fill(0.0);
-        for i in self.min_index..self.max_index {
+        for (i, &energy) in frame.iter().enumerate().take(self.max_index).skip(self.min_index) {
             let note = self.notes[i] as usize;
-            let energy = frame[i];
             if self.interpolate {
                 let mut note2 = note;
                 let mut a = 1.0;
@@ -85,12 +84,12 @@ fn freq_to_index(freq: u32, frame_size: usize, sample_rate: u32) -> usize {
 }
 
 fn index_to_freq(i: usize, frame_size: usize, sample_rate: u32) -> f64 {
-    return (i as f64) * sample_rate as f64 / frame_size as f64;
+    (i as f64) * sample_rate as f64 / frame_size as f64
 }
 
 fn freq_to_octave(freq: f64) -> f64 {
     let base = 440.0 / 16.0;
-    return f64::log2(freq / base);
+    f64::log2(freq / base)
 }
 
 #[cfg(test)]
@@ -80,6 +80,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn blur3() {
         let coefficients = [0.5, 0.7, 0.5];
         let mut image = Image::new(12);
@@ -64,8 +64,8 @@ mod tests {
         let mut normalized_data = data;
         normalize(&mut normalized_data, 0.01);
 
-        for i in 0..4 {
-            assert_eq_float!(normalized_data[i], 0.0, 1e-5);
+        for value in normalized_data {
+            assert_eq_float!(value, 0.0, 1e-5);
         }
     }
 
@@ -75,8 +75,8 @@ mod tests {
         let mut normalized_data = data;
         normalize(&mut normalized_data, 0.01);
 
-        for i in 0..4 {
-            assert_eq_float!(normalized_data[i], 0.0, 1e-5);
+        for value in normalized_data {
+            assert_eq_float!(value, 0.0, 1e-5);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,1412 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, Read};
+
+use rustfft::num_complex::Complex64;
+use rustfft::num_traits::Zero;
+use rustfft::FftPlanner;
+
+use crate::compression::{decompress_iter, decompress_stream_iter, estimate_length, DecompressError};
+use crate::fingerprint::Fingerprint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompareError {
+    /// Fingerprints were calculated with different algorithms.
+    AlgorithmMismatch(u8, u8),
+    /// There is no offset at which the fingerprints overlap enough.
+    NoOverlap,
+}
+
+impl Display for CompareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompareError::AlgorithmMismatch(a, b) => write!(f, "Cannot compare fingerprints of algorithms {a} and {b}"),
+            CompareError::NoOverlap => write!(f, "Fingerprints do not overlap"),
+        }
+    }
+}
+
+impl std::error::Error for CompareError {}
+
+/// Options controlling how fingerprints are aligned during comparison.
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    /// Maximal number of items by which one fingerprint can be shifted against the other.
+    pub max_offset: usize,
+
+    /// Number of items in a single window of [compare_detailed].
+    pub window_size: usize,
+
+    /// Minimal number of overlapping items of an alignment to be considered.
+    ///
+    /// Scores of a few overlapping items say little about the similarity of the fingerprints,
+    /// so such alignments are skipped. Alignments always overlap by at least one item.
+    pub min_overlap: usize,
+
+    /// Highest bit error rate reported as [MatchVerdict::Strong] by [classify_match].
+    pub strong_match_ber: f32,
+
+    /// Highest bit error rate reported as [MatchVerdict::Weak] by [classify_match].
+    pub weak_match_ber: f32,
+
+    /// Number of items ignored at both ends of each fingerprint.
+    ///
+    /// Fade-ins and fade-outs make the first and last items unreliable. Offsets keep
+    /// their meaning for the whole fingerprints, only the trimmed items are not compared.
+    pub trim_edges: usize,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            // About 15 seconds for the preset configurations.
+            max_offset: 120,
+            window_size: 100,
+            min_overlap: 1,
+            // Unrelated fingerprints differ in about half of their bits.
+            strong_match_ber: 0.15,
+            weak_match_ber: 0.35,
+            trim_edges: 0,
+        }
+    }
+}
+
+/// Result of comparing two fingerprints at their best alignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchScore {
+    /// Offset of the second fingerprint against the first one.
+    ///
+    /// For a positive offset `b[i]` is aligned with `a[i + offset]`,
+    /// for a negative one `a[i]` is aligned with `b[i - offset]`.
+    pub offset: isize,
+
+    /// Number of items that overlap at this offset.
+    pub overlap: usize,
+
+    /// Number of bits that differ between the overlapping items.
+    pub bit_errors: u64,
+}
+
+impl MatchScore {
+    /// Fraction of differing bits in the overlapping items.
+    ///
+    /// This value can be from 0 (identical) up to 1 (every bit differs).
+    pub fn bit_error_rate(&self) -> f32 {
+        if self.overlap == 0 {
+            return 1.0;
+        }
+        self.bit_errors as f32 / (self.overlap * 32) as f32
+    }
+
+    /// Similarity of the fingerprints, which is complementary to [MatchScore::bit_error_rate].
+    pub fn similarity(&self) -> f32 {
+        1.0 - self.bit_error_rate()
+    }
+}
+
+/// Result of [compare_detailed].
+#[derive(Debug, Clone)]
+pub struct DetailedMatch {
+    /// Score of the best alignment.
+    pub score: MatchScore,
+
+    /// Bit error rates of consecutive windows of the overlap at the best alignment.
+    ///
+    /// The last window might be shorter than [CompareOptions::window_size].
+    pub window_bit_error_rates: Vec<f32>,
+}
+
+impl DetailedMatch {
+    /// Summarizes the quality of the match over all of its windows.
+    pub fn summary(&self) -> MatchSummary {
+        let mut rates = self.window_bit_error_rates.clone();
+        rates.sort_by(f32::total_cmp);
+
+        let len = rates.len();
+        let mean = if len == 0 { 0.0 } else { rates.iter().sum::<f32>() / len as f32 };
+        let median = match len {
+            0 => 0.0,
+            _ if len % 2 == 0 => (rates[len / 2 - 1] + rates[len / 2]) / 2.0,
+            _ => rates[len / 2],
+        };
+        MatchSummary {
+            best_offset: self.score.offset,
+            best_bit_error_rate: self.score.bit_error_rate(),
+            worst_window_bit_error_rate: rates.last().copied().unwrap_or(0.0),
+            mean_window_bit_error_rate: mean,
+            median_window_bit_error_rate: median,
+        }
+    }
+}
+
+/// Result of [match_with_drift].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriftMatch {
+    /// Score of the best alignment.
+    ///
+    /// The offset applies to the first item of the second fingerprint,
+    /// later items are shifted further by the drift.
+    pub score: MatchScore,
+
+    /// Drift of the second fingerprint against the first one in parts per million.
+    ///
+    /// For a positive drift `b[j]` is aligned with `a[offset + j + round(j * drift_ppm / 1e6)]`,
+    /// so the second fingerprint runs faster than the first one.
+    pub drift_ppm: i32,
+}
+
+/// Verdict of [classify_match].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchVerdict {
+    /// The fingerprints very likely come from the same recording.
+    Strong { offset: isize, ber: f32 },
+
+    /// The fingerprints are more similar than unrelated ones, but might still not match.
+    Weak { offset: isize, ber: f32 },
+
+    /// The fingerprints do not match at any of the considered offsets.
+    NoMatch,
+}
+
+/// Summary statistics of a [DetailedMatch].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSummary {
+    /// Offset of the best alignment, see [MatchScore::offset].
+    pub best_offset: isize,
+
+    /// Bit error rate of the whole overlap at the best alignment.
+    pub best_bit_error_rate: f32,
+
+    /// Highest bit error rate of a single window at the best alignment.
+    pub worst_window_bit_error_rate: f32,
+
+    /// Mean of the bit error rates of windows at the best alignment.
+    pub mean_window_bit_error_rate: f32,
+
+    /// Median of the bit error rates of windows at the best alignment.
+    pub median_window_bit_error_rate: f32,
+}
+
+/// Finds an alignment of two fingerprints with the lowest bit error rate.
+///
+/// Ties are broken by preferring the offset closest to zero, and then the negative one.
+/// Returns `None` if the fingerprints do not overlap by at least [CompareOptions::min_overlap]
+/// items at any of the considered offsets.
+///
+/// No check is performed whether both fingerprints come from the same algorithm,
+/// see [compare_checked] for that.
+pub fn compare_fingerprints(a: &[u32], b: &[u32], opts: &CompareOptions) -> Option<MatchScore> {
+    let (a, b) = (trim_edges(a, opts), trim_edges(b, opts));
+    let max_offset = reachable_offset(a, b, opts.max_offset);
+    best_alignment(a, b, -max_offset..=max_offset, opts.min_overlap)
+}
+
+/// Compares two fingerprints like [compare_fingerprints] and tells whether they match.
+///
+/// The bit error rate of the best alignment is compared with [CompareOptions::strong_match_ber]
+/// and [CompareOptions::weak_match_ber], by default 0.15 and 0.35. Fingerprints that do not
+/// overlap enough are reported as [MatchVerdict::NoMatch].
+pub fn classify_match(a: &[u32], b: &[u32], opts: &CompareOptions) -> MatchVerdict {
+    let Some(score) = compare_fingerprints(a, b, opts) else {
+        return MatchVerdict::NoMatch;
+    };
+    let (offset, ber) = (score.offset, score.bit_error_rate());
+    if ber <= opts.strong_match_ber {
+        MatchVerdict::Strong { offset, ber }
+    } else if ber <= opts.weak_match_ber {
+        MatchVerdict::Weak { offset, ber }
+    } else {
+        MatchVerdict::NoMatch
+    }
+}
+
+/// Finds an alignment like [compare_fingerprints], but independently of the order of the fingerprints.
+///
+/// Both directions of sliding are always checked, but ties between equally good offsets
+/// depend on their sign, so swapping the arguments of [compare_fingerprints] can pick
+/// a different alignment. Here the result for `(b, a)` is always the one for `(a, b)`
+/// with a negated [MatchScore::offset], which keeps its usual meaning for `(a, b)`.
+pub fn compare_symmetric(a: &[u32], b: &[u32], opts: &CompareOptions) -> Option<MatchScore> {
+    if a <= b {
+        return compare_fingerprints(a, b, opts);
+    }
+    let score = compare_fingerprints(b, a, opts)?;
+    Some(MatchScore { offset: -score.offset, ..score })
+}
+
+/// Finds an alignment like [compare_fingerprints], but first checks only every `coarse_step`-th offset
+/// and then refines the best of them by checking all the offsets up to `coarse_step` around it.
+///
+/// Fingerprints of real audio change gradually, so the error rate grows smoothly around the true
+/// offset and this usually finds the same alignment with far fewer comparisons. It can however
+/// miss a narrow minimum between the sampled offsets. A `coarse_step` of 0 is treated as 1.
+/// Alignments overlapping by fewer than `min_overlap` items are skipped, see [CompareOptions::min_overlap].
+pub fn match_coarse_to_fine(
+    a: &[u32],
+    b: &[u32],
+    max_offset: usize,
+    coarse_step: usize,
+    min_overlap: usize,
+) -> Option<MatchScore> {
+    let max_offset = reachable_offset(a, b, max_offset);
+    let coarse_step = coarse_step.max(1);
+
+    // The sampled offsets are symmetric around 0 and always include both ends of the range.
+    let coarse = (0..=max_offset)
+        .step_by(coarse_step)
+        .chain([max_offset])
+        .flat_map(|offset| [offset, -offset]);
+    let rough = best_alignment(a, b, coarse, min_overlap)?;
+
+    let coarse_step = coarse_step as isize;
+    let start = isize::max(rough.offset - coarse_step, -max_offset);
+    let end = isize::min(rough.offset + coarse_step, max_offset);
+    best_alignment(a, b, start..=end, min_overlap)
+}
+
+/// Finds an alignment like [compare_fingerprints], but also lets the second fingerprint drift
+/// linearly against the first one by up to `max_drift_ppm` parts per million.
+///
+/// Recordings played back at slightly different speeds drift apart over time, so at a single
+/// offset only their beginnings match. Drifts are checked in steps that move the last item
+/// of `b` by one position, and ties between equally good drifts prefer the one closest to zero.
+/// Returns `None` if the fingerprints do not overlap by at least `min_overlap` items
+/// at any of the considered alignments.
+pub fn match_with_drift(
+    a: &[u32],
+    b: &[u32],
+    max_offset: usize,
+    max_drift_ppm: u32,
+    min_overlap: usize,
+) -> Option<DriftMatch> {
+    let max_drift = i32::try_from(max_drift_ppm).unwrap_or(i32::MAX);
+    // Drifting moves the items of `b` by up to `max_shift` positions, so a few more offsets overlap.
+    let max_shift = (b.len() as f64 * f64::from(max_drift) / 1e6).ceil() as usize;
+    let reach = a.len().max(b.len()).saturating_add(max_shift);
+    let max_offset = max_offset.min(reach).min(isize::MAX as usize) as isize;
+    let step = i32::try_from(1_000_000usize.div_ceil(b.len().max(1))).unwrap_or(i32::MAX);
+
+    let drifts = std::iter::once(0).chain((1..=max_drift / step).flat_map(|k| [k * step, -k * step]));
+    let mut best: Option<DriftMatch> = None;
+    for drift_ppm in drifts {
+        let scores = (-max_offset..=max_offset)
+            .filter_map(|offset| score_with_drift(a, b, offset, drift_ppm, min_overlap));
+        for score in scores {
+            if best.as_ref().is_none_or(|best| is_better(&score, &best.score)) {
+                best = Some(DriftMatch { score, drift_ppm });
+            }
+        }
+    }
+    best
+}
+
+/// Finds the same alignment as [compare_fingerprints], but scores all the offsets at once
+/// by cross-correlating the fingerprints with FFT.
+///
+/// Every bit of both fingerprints becomes a signal of +1 and -1, so the sum of correlations
+/// of all 32 bit positions at an offset is the number of agreeing bits minus the number of
+/// differing ones. This takes O(n log n) time instead of O(n * max_offset), which pays off
+/// for long fingerprints and large offsets. The best alignment is scored again directly.
+pub fn match_fft(a: &[u32], b: &[u32], opts: &CompareOptions) -> Option<MatchScore> {
+    let (a, b) = (trim_edges(a, opts), trim_edges(b, opts));
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Padding to the full length of the correlation keeps offsets from wrapping around.
+    let size = (a.len() + b.len() - 1).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+
+    let mut correlation = vec![Complex64::zero(); size];
+    let (mut a_plane, mut b_plane) = (vec![Complex64::zero(); size], vec![Complex64::zero(); size]);
+    for p in 0..32 {
+        bit_signal(a, p, &mut a_plane);
+        bit_signal(b, p, &mut b_plane);
+        forward.process(&mut a_plane);
+        forward.process(&mut b_plane);
+        for ((sum, x), y) in correlation.iter_mut().zip(&a_plane).zip(&b_plane) {
+            *sum += x * y.conj();
+        }
+    }
+    inverse.process(&mut correlation);
+
+    let max_offset = reachable_offset(a, b, opts.max_offset);
+    let mut best: Option<MatchScore> = None;
+    for offset in -max_offset..=max_offset {
+        let overlap = aligned_overlap(a, b, offset).0.len();
+        if overlap == 0 || overlap < opts.min_overlap {
+            continue;
+        }
+        // The inverse transform is not normalized. Correlation at a negative offset wraps to the end.
+        let agreement = correlation[offset.rem_euclid(size as isize) as usize].re / size as f64;
+        let bit_errors = ((32 * overlap) as f64 - agreement) / 2.0;
+        let candidate = MatchScore { offset, overlap, bit_errors: bit_errors.round().max(0.0) as u64 };
+        if best.as_ref().is_none_or(|best| is_better(&candidate, best)) {
+            best = Some(candidate);
+        }
+    }
+    best.and_then(|best| score_at(a, b, best.offset, opts.min_overlap))
+}
+
+/// Writes bit `p` of every subfingerprint to `signal` as +1 or -1, padding the rest with zeros.
+fn bit_signal(fp: &[u32], p: u32, signal: &mut [Complex64]) {
+    for (i, value) in signal.iter_mut().enumerate() {
+        let re = match fp.get(i) {
+            Some(sub_fp) if (sub_fp >> p) & 1 == 1 => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        };
+        *value = Complex64::new(re, 0.0);
+    }
+}
+
+/// Finds the same alignment as [compare_fingerprints], but checks offsets in parallel.
+#[cfg(feature = "rayon")]
+pub fn compare_fingerprints_parallel(a: &[u32], b: &[u32], opts: &CompareOptions) -> Option<MatchScore> {
+    use rayon::prelude::*;
+
+    let (a, b) = (trim_edges(a, opts), trim_edges(b, opts));
+    let max_offset = reachable_offset(a, b, opts.max_offset);
+    (-max_offset..=max_offset)
+        .into_par_iter()
+        .filter_map(|offset| score_at(a, b, offset, opts.min_overlap))
+        .reduce_with(|best, candidate| if is_better(&candidate, &best) { candidate } else { best })
+}
+
+/// Finds the same alignment as [compare_fingerprints], but reads both fingerprints
+/// directly from their compressed form.
+///
+/// Both are decompressed in lockstep and only their last `max_offset + 1` subfingerprints
+/// are kept, so the memory needed does not grow with their length.
+/// Like [compare_fingerprints] this does not check the algorithms of the fingerprints.
+pub fn compare_compressed(a: &[u8], b: &[u8], opts: &CompareOptions) -> Result<Option<MatchScore>, DecompressError> {
+    let (a_length, a_items) = trimmed_items(a, opts.trim_edges)?;
+    let (b_length, b_items) = trimmed_items(b, opts.trim_edges)?;
+    compare_items(a_items, b_items, a_length, b_length, opts)
+}
+
+/// Finds the alignment of [compare_compressed] in subfingerprints decompressed on demand.
+///
+/// The lengths from the headers of both fingerprints limit the buffers to the offsets that can overlap.
+fn compare_items(
+    mut a_items: impl Iterator<Item = Result<u32, DecompressError>>,
+    mut b_items: impl Iterator<Item = Result<u32, DecompressError>>,
+    a_length: usize,
+    b_length: usize,
+    opts: &CompareOptions,
+) -> Result<Option<MatchScore>, DecompressError> {
+    let max_offset = opts.max_offset.min(a_length.max(b_length));
+    let history = max_offset + 1;
+    let (mut a_recent, mut b_recent) = (vec![0; history], vec![0; history]);
+    let (mut a_len, mut b_len) = (0, 0);
+    // Bit errors and overlaps of offsets -max_offset..=max_offset.
+    let mut bit_errors = vec![0u64; 2 * max_offset + 1];
+    let mut overlaps = vec![0usize; 2 * max_offset + 1];
+
+    for i in 0.. {
+        let a_item = a_items.next().transpose()?;
+        let b_item = b_items.next().transpose()?;
+        if a_item.is_none() && b_item.is_none() {
+            break;
+        }
+
+        if let Some(a_item) = a_item {
+            a_recent[i % history] = a_item;
+            a_len += 1;
+        }
+        if let Some(b_item) = b_item {
+            b_recent[i % history] = b_item;
+            b_len += 1;
+        }
+
+        // New items are paired with the items up to `max_offset` before them in the other fingerprint.
+        for d in 0..=usize::min(max_offset, i) {
+            let j = i - d;
+            if a_item.is_some() && j < b_len {
+                bit_errors[max_offset + d] += u64::from((a_recent[i % history] ^ b_recent[j % history]).count_ones());
+                overlaps[max_offset + d] += 1;
+            }
+            if d > 0 && b_item.is_some() && j < a_len {
+                bit_errors[max_offset - d] += u64::from((a_recent[j % history] ^ b_recent[i % history]).count_ones());
+                overlaps[max_offset - d] += 1;
+            }
+        }
+    }
+
+    let mut best: Option<MatchScore> = None;
+    for (i, (&bit_errors, &overlap)) in bit_errors.iter().zip(&overlaps).enumerate() {
+        let candidate = MatchScore { offset: i as isize - max_offset as isize, overlap, bit_errors };
+        if overlap >= opts.min_overlap.max(1) && best.as_ref().is_none_or(|best| is_better(&candidate, best)) {
+            best = Some(candidate);
+        }
+    }
+    Ok(best)
+}
+
+/// Finds the same alignment as [compare_compressed], but reads both compressed fingerprints
+/// from readers.
+///
+/// Extensions are stored after all the spans, so only the spans of both fingerprints are buffered.
+/// The extensions are read while the fingerprints are decompressed in lockstep, and only as many
+/// subfingerprints are kept as in [compare_compressed]. The readers are buffered internally.
+/// Errors of the readers are returned as they are, and invalid data is reported as an error
+/// of kind [std::io::ErrorKind::InvalidData].
+pub fn compare_readers<R1: Read, R2: Read>(a: R1, b: R2, opts: &CompareOptions) -> std::io::Result<Option<MatchScore>> {
+    let (mut a_error, mut b_error) = (None, None);
+    let score = compare_streams(&mut reader_bytes(a, &mut a_error), &mut reader_bytes(b, &mut b_error), opts);
+    // A failed read ends the data early, so it is the cause of any decompression error.
+    if let Some(err) = a_error.or(b_error) {
+        return Err(err);
+    }
+    Ok(score?)
+}
+
+/// Finds the alignment of [compare_compressed] in compressed fingerprints read from streams of bytes.
+fn compare_streams(
+    a: &mut impl Iterator<Item = u8>,
+    b: &mut impl Iterator<Item = u8>,
+    opts: &CompareOptions,
+) -> Result<Option<MatchScore>, DecompressError> {
+    let (_, a_length, a_items) = decompress_stream_iter(&mut *a)?;
+    let (_, b_length, b_items) = decompress_stream_iter(&mut *b)?;
+    let a_items = trim_items(a_items, a_length, opts.trim_edges);
+    let b_items = trim_items(b_items, b_length, opts.trim_edges);
+    let score = compare_items(a_items, b_items, a_length, b_length, opts)?;
+    if a.next().is_some() || b.next().is_some() {
+        return Err(DecompressError::TrailingData);
+    }
+    Ok(score)
+}
+
+/// Returns the bytes of a reader until its end or its first error, which is stored in `error`.
+fn reader_bytes<'a, R: Read + 'a>(reader: R, error: &'a mut Option<std::io::Error>) -> impl Iterator<Item = u8> + 'a {
+    BufReader::new(reader).bytes().map_while(|byte| byte.map_err(|err| *error = Some(err)).ok())
+}
+
+/// Scores how well a short fingerprint, such as a clip, is contained in a long one.
+///
+/// The short fingerprint is placed entirely within the first `max_offset + short.len()` items
+/// of the long one, and the best placement is scored by the similarity over the whole length of
+/// the short fingerprint, from 0 to 1. Returns 0 if the short fingerprint is empty, does not fit,
+/// or is shorter than `min_overlap` items, as it always overlaps with the long one entirely.
+pub fn containment_score(short: &[u32], long: &[u32], max_offset: usize, min_overlap: usize) -> f32 {
+    if short.is_empty() || short.len() > long.len() || short.len() < min_overlap {
+        return 0.0;
+    }
+    let last_offset = usize::min(max_offset, long.len() - short.len());
+    let bit_errors = (0..=last_offset)
+        .map(|offset| fingerprint_distance(&long[offset..], short))
+        .min()
+        .unwrap_or(0);
+    1.0 - bit_errors as f32 / (short.len() * 32) as f32
+}
+
+/// Jaccard similarity of the sets of distinct subfingerprints of two fingerprints, from 0 to 1.
+///
+/// This ignores the order of subfingerprints, so it does not depend on their alignment, but it is
+/// much coarser than the bit error rate of [compare_fingerprints]: subfingerprints differing in
+/// a single bit count as completely different. Returns 0 if both fingerprints are empty.
+pub fn jaccard_similarity(a: &[u32], b: &[u32]) -> f32 {
+    let a = a.iter().collect::<HashSet<_>>();
+    let b = b.iter().collect::<HashSet<_>>();
+    let intersection = a.intersection(&b).count();
+    let union = a.len() + b.len() - intersection;
+    if union == 0 {
+        return 0.0;
+    }
+    intersection as f32 / union as f32
+}
+
+/// Stitches two overlapping fingerprints, such as of consecutive chunks of a recording, into one.
+///
+/// `b` is aligned so that it starts within the last `max_offset` items of `a`. If the best such
+/// alignment differs in fewer than 10 bits per item, the threshold which [crate::match_fingerprints]
+/// uses for matching segments, `a` is extended by the items of `b` past its end.
+/// Alignments overlapping by fewer than `min_overlap` items are skipped, see [CompareOptions::min_overlap].
+/// Returns `None` if the fingerprints do not overlap well enough.
+pub fn merge_overlapping(a: &[u32], b: &[u32], max_offset: usize, min_overlap: usize) -> Option<Vec<u32>> {
+    const MAX_BIT_ERRORS_PER_ITEM: u64 = 10;
+
+    let start = a.len().saturating_sub(max_offset) as isize;
+    let best = best_alignment(a, b, start..a.len() as isize, min_overlap)?;
+    if best.bit_errors >= MAX_BIT_ERRORS_PER_ITEM * best.overlap as u64 {
+        return None;
+    }
+
+    // The offset is not negative, so the overlap is either the end of `a` or the whole `b`.
+    Some([a, &b[best.overlap..]].concat())
+}
+
+/// Compares a query against many candidates and ranks those that overlap with it.
+///
+/// Returns indices of the candidates together with their scores, from the most similar one.
+/// Candidates with equal scores keep their order.
+pub fn rank_matches(query: &[u32], candidates: &[&[u32]], opts: &CompareOptions) -> Vec<(usize, MatchScore)> {
+    let mut ranked = candidates.iter()
+        .enumerate()
+        .filter_map(|(i, candidate)| Some((i, compare_fingerprints(query, candidate, opts)?)))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| {
+        if is_better(a, b) {
+            std::cmp::Ordering::Less
+        } else if is_better(b, a) {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    ranked
+}
+
+/// Finds the best alignment like [compare_fingerprints] and additionally measures
+/// the bit error rate in windows along the overlap.
+///
+/// This shows which parts of the overlap match, for example when only a part of a track was reused.
+pub fn compare_detailed(a: &[u32], b: &[u32], opts: &CompareOptions) -> Option<DetailedMatch> {
+    let score = compare_fingerprints(a, b, opts)?;
+    let (a, b) = aligned_overlap(trim_edges(a, opts), trim_edges(b, opts), score.offset);
+    let window_bit_error_rates = a.chunks(opts.window_size.max(1))
+        .zip(b.chunks(opts.window_size.max(1)))
+        .map(|(a, b)| fingerprint_distance(a, b) as f32 / (a.len() * 32) as f32)
+        .collect();
+    Some(DetailedMatch { score, window_bit_error_rates })
+}
+
+/// Compares two fingerprints, making sure that they were calculated with the same algorithm.
+pub fn compare_checked(a: &Fingerprint, b: &Fingerprint) -> Result<MatchScore, CompareError> {
+    if a.algorithm() != b.algorithm() {
+        return Err(CompareError::AlgorithmMismatch(a.algorithm(), b.algorithm()));
+    }
+
+    compare_fingerprints(a.data(), b.data(), &CompareOptions::default())
+        .ok_or(CompareError::NoOverlap)
+}
+
+/// Scores the similarity of two fingerprints like the AcoustID server does.
+///
+/// This follows `acoustid_compare2` from [pg_acoustid](https://github.com/acoustid/pg_acoustid),
+/// with a maximal offset of 80 as used by the server. The best offset is voted by the 14 most
+/// significant bits of subfingerprints, the bit error rate of the overlap is mapped to a score
+/// from 0 to 1, and fingerprints with few distinct values are penalized.
+///
+/// Quirks of the original, such as ignoring the first subfingerprint when voting,
+/// are kept so that the scores match.
+pub fn acoustid_score(a: &[u32], b: &[u32]) -> f32 {
+    const MATCH_BITS: u32 = 14;
+    const MATCH_MASK: usize = (1 << MATCH_BITS) - 1;
+    const MAX_OFFSET: isize = 80;
+
+    let key = |sub_fp: u32| (sub_fp >> (32 - MATCH_BITS)) as usize;
+
+    // Last position of each key, 0 doubles as a marker of a missing one.
+    let mut a_offsets = vec![0; MATCH_MASK + 1];
+    let mut b_offsets = vec![0; MATCH_MASK + 1];
+    for (i, &sub_fp) in a.iter().enumerate() {
+        a_offsets[key(sub_fp)] = i;
+    }
+    for (i, &sub_fp) in b.iter().enumerate() {
+        b_offsets[key(sub_fp)] = i;
+    }
+
+    let mut counts = vec![0; a.len() + b.len()];
+    let mut top_count = 0;
+    let mut top_offset = 0;
+    for (&a_offset, &b_offset) in a_offsets.iter().zip(&b_offsets).take(MATCH_MASK) {
+        if a_offset == 0 || b_offset == 0 {
+            continue;
+        }
+        let offset = a_offset as isize - b_offset as isize;
+        if offset.abs() > MAX_OFFSET {
+            continue;
+        }
+        let count = &mut counts[(offset + b.len() as isize) as usize];
+        *count += 1;
+        if *count > top_count {
+            top_count = *count;
+            top_offset = offset;
+        }
+    }
+
+    let min_size = usize::min(a.len(), b.len()) & !1;
+    let a = &a[top_offset.max(0).unsigned_abs().min(a.len())..];
+    let b = &b[top_offset.min(0).unsigned_abs().min(b.len())..];
+    let size = usize::min(a.len(), b.len()) / 2;
+    if size == 0 || min_size == 0 {
+        return 0.0;
+    }
+
+    let a_unique = unique_keys(a, key);
+    let b_unique = unique_keys(b, key);
+    let diversity = f32::min(
+        f32::min(1.0, (a_unique + 10) as f32 / a.len() as f32 + 0.5),
+        f32::min(1.0, (b_unique + 10) as f32 / b.len() as f32 + 0.5),
+    );
+    if (top_count as f32) < usize::max(a_unique, b_unique) as f32 * 0.02 {
+        return 0.0;
+    }
+
+    let bit_errors = fingerprint_distance(&a[..2 * size], &b[..2 * size]);
+    let score = (size as f32 * 2.0 / min_size as f32) * (1.0 - 2.0 * bit_errors as f32 / (64 * size) as f32);
+    let score = score.max(0.0);
+    if diversity < 1.0 {
+        score.powf(8.0 - 7.0 * diversity)
+    } else {
+        score
+    }
+}
+
+fn unique_keys(fp: &[u32], key: impl Fn(u32) -> usize) -> usize {
+    let mut seen = HashSet::new();
+    fp.iter().filter(|&&sub_fp| seen.insert(key(sub_fp))).count()
+}
+
+/// Counts differing bits between subfingerprints at the same positions.
+///
+/// Only the first `min(a.len(), b.len())` subfingerprints are compared.
+pub fn fingerprint_distance(a: &[u32], b: &[u32]) -> u64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| u64::from((x ^ y).count_ones()))
+        .sum()
+}
+
+/// Counts differing bits like [fingerprint_distance], but against the bitwise
+/// complement of `b`, without having to invert it first.
+pub fn fingerprint_distance_inverted(a: &[u32], b: &[u32]) -> u64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| u64::from((x ^ !y).count_ones()))
+        .sum()
+}
+
+/// Drops [CompareOptions::trim_edges] items from both ends of a fingerprint.
+fn trim_edges<'a>(fp: &'a [u32], opts: &CompareOptions) -> &'a [u32] {
+    fp.get(opts.trim_edges..fp.len().saturating_sub(opts.trim_edges)).unwrap_or_default()
+}
+
+/// Decompresses the items of a fingerprint left by [trim_edges], still reporting errors in the trimmed ones.
+/// Returns the length of the whole fingerprint along with them.
+fn trimmed_items(
+    compressed: &[u8],
+    trim: usize,
+) -> Result<(usize, impl Iterator<Item = Result<u32, DecompressError>> + '_), DecompressError> {
+    let length = estimate_length(compressed)?;
+    let (_, items) = decompress_iter(compressed)?;
+    Ok((length, trim_items(items, length, trim)))
+}
+
+/// Skips `trim` subfingerprints at both ends of a fingerprint of `length` items decompressed on demand.
+///
+/// Errors are passed through wherever they occur.
+fn trim_items(
+    items: impl Iterator<Item = Result<u32, DecompressError>>,
+    length: usize,
+    trim: usize,
+) -> impl Iterator<Item = Result<u32, DecompressError>> {
+    let kept = trim..length.saturating_sub(trim);
+    items.enumerate().filter(move |(i, item)| item.is_err() || kept.contains(i)).map(|(_, item)| item)
+}
+
+/// Limits `max_offset` to the offsets at which the fingerprints overlap at all.
+fn reachable_offset(a: &[u32], b: &[u32], max_offset: usize) -> isize {
+    // Slices never hold more than isize::MAX items, so this does not wrap.
+    max_offset.min(a.len().max(b.len())) as isize
+}
+
+/// Scores the alignment at the given offset, unless the fingerprints overlap there
+/// by fewer than `min_overlap` items or do not overlap at all.
+fn score_at(a: &[u32], b: &[u32], offset: isize, min_overlap: usize) -> Option<MatchScore> {
+    let (a, b) = aligned_overlap(a, b, offset);
+    if a.is_empty() || a.len() < min_overlap {
+        return None;
+    }
+    Some(MatchScore { offset, overlap: a.len(), bit_errors: fingerprint_distance(a, b) })
+}
+
+/// Scores the alignment at the given offset and drift, see [DriftMatch::drift_ppm], unless
+/// the fingerprints overlap there by fewer than `min_overlap` items or do not overlap at all.
+fn score_with_drift(a: &[u32], b: &[u32], offset: isize, drift_ppm: i32, min_overlap: usize) -> Option<MatchScore> {
+    let mut overlap = 0;
+    let mut bit_errors = 0;
+    for (j, y) in b.iter().enumerate() {
+        let shift = (j as f64 * f64::from(drift_ppm) / 1e6).round() as isize;
+        let Some(x) = usize::try_from(offset + j as isize + shift).ok().and_then(|i| a.get(i)) else {
+            continue;
+        };
+        overlap += 1;
+        bit_errors += u64::from((x ^ y).count_ones());
+    }
+    (overlap > 0 && overlap >= min_overlap).then_some(MatchScore { offset, overlap, bit_errors })
+}
+
+/// Finds the best of the alignments at the given offsets, see [is_better].
+fn best_alignment(
+    a: &[u32],
+    b: &[u32],
+    offsets: impl IntoIterator<Item = isize>,
+    min_overlap: usize,
+) -> Option<MatchScore> {
+    let mut best: Option<MatchScore> = None;
+    for candidate in offsets.into_iter().filter_map(|offset| score_at(a, b, offset, min_overlap)) {
+        if best.as_ref().is_none_or(|best| is_better(&candidate, best)) {
+            best = Some(candidate);
+        }
+    }
+    best
+}
+
+/// Orders scores by their bit error rate, then by the distance of the offset from zero
+/// and finally by the offset itself, so the best score does not depend on the order of checks.
+fn is_better(candidate: &MatchScore, best: &MatchScore) -> bool {
+    // Compare bit error rates without dividing: e1 / n1 < e2 / n2 <=> e1 * n2 < e2 * n1
+    let lhs = u128::from(candidate.bit_errors) * best.overlap as u128;
+    let rhs = u128::from(best.bit_errors) * candidate.overlap as u128;
+    (lhs, candidate.offset.unsigned_abs(), candidate.offset) < (rhs, best.offset.unsigned_abs(), best.offset)
+}
+
+/// Returns parts of both fingerprints that overlap at the given offset.
+///
+/// The offset has the same meaning as [MatchScore::offset], so the best alignment
+/// found by [compare_fingerprints] can be inspected further. Both parts have the same
+/// length and are empty if the fingerprints do not overlap.
+pub fn aligned_overlap<'a>(a: &'a [u32], b: &'a [u32], offset: isize) -> (&'a [u32], &'a [u32]) {
+    let a_start = offset.max(0).unsigned_abs().min(a.len());
+    let b_start = offset.min(0).unsigned_abs().min(b.len());
+    let size = usize::min(a.len() - a_start, b.len() - b_start);
+    (&a[a_start..][..size], &b[b_start..][..size])
+}
+
+/// Lists indices `(i, j)` of the items `a[i]` and `b[j]` aligned at the given offset
+/// which differ in at most `max_bit_diff` bits.
+///
+/// The offset has the same meaning as [MatchScore::offset], so the pairs of the best alignment
+/// show which parts of the fingerprints match tightly. Pairs are ordered by their indices.
+pub fn matching_pairs(a: &[u32], b: &[u32], offset: isize, max_bit_diff: u32) -> Vec<(usize, usize)> {
+    let a_start = offset.max(0).unsigned_abs();
+    let b_start = offset.min(0).unsigned_abs();
+    let (a, b) = aligned_overlap(a, b, offset);
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .filter(|(_, (a, b))| (*a ^ *b).count_ones() <= max_bit_diff)
+        .map(|(k, _)| (a_start + k, b_start + k))
+        .collect()
+}
+
+/// Counts how many subfingerprints have to be inserted, deleted or replaced to turn `a` into `b`.
+///
+/// Subfingerprints differing in at most `max_bit_diff` bits are treated as equal. Unlike
+/// alignment at a single offset, this handles structural edits such as a removed verse.
+/// Only edits within a band of diagonals are considered, and the band is doubled until it
+/// provably contains the result, so this takes O(n * d) time for a distance of d.
+pub fn edit_distance(a: &[u32], b: &[u32], max_bit_diff: u32) -> usize {
+    let mut band = a.len().abs_diff(b.len()).max(1);
+    loop {
+        let distance = banded_edit_distance(a, b, max_bit_diff, band);
+        if distance <= band {
+            return distance;
+        }
+        band *= 2;
+    }
+}
+
+/// Computes [edit_distance] considering only the items `a[i]` and `b[j]` with `|i - j| <= band`.
+///
+/// The result is exact if it does not exceed `band`, otherwise it is only an upper bound.
+fn banded_edit_distance(a: &[u32], b: &[u32], max_bit_diff: u32, band: usize) -> usize {
+    const UNREACHABLE: usize = usize::MAX / 2;
+    if a.len().abs_diff(b.len()) > band {
+        return UNREACHABLE;
+    }
+
+    // Cell `k` of a row `i` holds the distance between `a[..i]` and `b[..i + k - band]`.
+    let width = 2 * band + 1;
+    let mut prev = vec![UNREACHABLE; width];
+    let mut row = vec![UNREACHABLE; width];
+    for (j, cell) in prev[band..].iter_mut().take(b.len() + 1).enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for k in 0..width {
+            row[k] = match (i + k).checked_sub(band) {
+                Some(0) => i,
+                Some(j) if j <= b.len() => {
+                    let cost = usize::from((a[i - 1] ^ b[j - 1]).count_ones() > max_bit_diff);
+                    let replaced = prev[k] + cost;
+                    let deleted = prev.get(k + 1).map_or(UNREACHABLE, |d| d + 1);
+                    let inserted = k.checked_sub(1).map_or(UNREACHABLE, |k| row[k] + 1);
+                    replaced.min(deleted).min(inserted)
+                }
+                _ => UNREACHABLE,
+            };
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[b.len() + band - a.len()]
+}
+
+/// Fraction of the items aligned at the given offset that agree in each of the 32 bit positions.
+///
+/// Bits of subfingerprints come from different classifiers, so this shows whether differences
+/// cluster in a few noisy positions. All the fractions are 0 if the fingerprints do not overlap.
+pub fn bit_agreement(a: &[u32], b: &[u32], offset: isize) -> [f32; 32] {
+    let (a, b) = aligned_overlap(a, b, offset);
+    let mut agreeing = [0usize; 32];
+    for (x, y) in a.iter().zip(b) {
+        let same = !(x ^ y);
+        for (p, count) in agreeing.iter_mut().enumerate() {
+            *count += ((same >> p) & 1) as usize;
+        }
+    }
+    agreeing.map(|count| if a.is_empty() { 0.0 } else { count as f32 / a.len() as f32 })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::comparison::{
+        acoustid_score, aligned_overlap, bit_agreement, classify_match, compare_checked, compare_compressed,
+        compare_detailed, compare_fingerprints, compare_readers, compare_symmetric, containment_score, edit_distance,
+        fingerprint_distance, fingerprint_distance_inverted, jaccard_similarity, match_coarse_to_fine, match_fft,
+        match_with_drift, matching_pairs, merge_overlapping, rank_matches, CompareError, CompareOptions, MatchVerdict,
+    };
+    use crate::compression::{compress_fingerprint, DecompressError};
+    use crate::assert_eq_float;
+    use crate::fingerprint::Fingerprint;
+    use crate::utils::{matching_pair, pseudo_random};
+
+    #[test]
+    fn finds_shifted_alignment() {
+        let a = pseudo_random(1, 200);
+        let b = a[7..].to_vec();
+
+        let score = compare_fingerprints(&a, &b, &CompareOptions::default()).unwrap();
+        assert_eq!(score.offset, 7);
+        assert_eq!(score.overlap, 193);
+        assert_eq!(score.bit_errors, 0);
+
+        let score = compare_fingerprints(&b, &a, &CompareOptions::default()).unwrap();
+        assert_eq!(score.offset, -7);
+        assert_eq!(score.bit_errors, 0);
+    }
+
+    #[test]
+    fn unbounded_max_offset() {
+        let opts = CompareOptions { max_offset: usize::MAX, ..CompareOptions::default() };
+        let score = compare_fingerprints(&[1, 2, 3], &[1, 2, 3], &opts).unwrap();
+        assert_eq!((score.offset, score.overlap, score.bit_errors), (0, 3, 0));
+
+        let a = pseudo_random(1, 200);
+        let bounded = CompareOptions { max_offset: 300, ..CompareOptions::default() };
+        for b in [a[150..].to_vec(), pseudo_random(2, 300)] {
+            assert_eq!(compare_fingerprints(&a, &b, &opts), compare_fingerprints(&a, &b, &bounded));
+            assert_eq!(match_fft(&a, &b, &opts), compare_fingerprints(&a, &b, &bounded));
+            assert_eq!(match_coarse_to_fine(&a, &b, usize::MAX, 8, 1), match_coarse_to_fine(&a, &b, 300, 8, 1));
+        }
+        assert_eq!(match_coarse_to_fine(&[1, 2, 3], &[1, 2, 3], usize::MAX, 8, 1), Some(score.clone()));
+
+        let drifting = match_with_drift(&[1, 2, 3], &[1, 2, 3], usize::MAX, 5000, 1).unwrap();
+        assert_eq!((drifting.score, drifting.drift_ppm), (score, 0));
+        // A drift of 5000 ppm moves the last items of a 300 items long fingerprint by up to 2 positions.
+        let b = pseudo_random(2, 300);
+        assert_eq!(match_with_drift(&a, &b, usize::MAX, 5000, 1), match_with_drift(&a, &b, 302, 5000, 1));
+    }
+
+    #[test]
+    fn verdicts() {
+        let opts = CompareOptions::default();
+        let a = pseudo_random(1, 300);
+        let b = a[10..].to_vec();
+        assert_eq!(classify_match(&a, &b, &opts), MatchVerdict::Strong { offset: 10, ber: 0.0 });
+
+        // Every item differs in 8 of its 32 bits.
+        let noisy = b.iter().map(|sub_fp| sub_fp ^ 0xff00).collect::<Vec<_>>();
+        assert_eq!(classify_match(&a, &noisy, &opts), MatchVerdict::Weak { offset: 10, ber: 0.25 });
+        let strict = CompareOptions { weak_match_ber: 0.2, ..CompareOptions::default() };
+        assert_eq!(classify_match(&a, &noisy, &strict), MatchVerdict::NoMatch);
+
+        assert_eq!(classify_match(&a, &pseudo_random(2, 300), &opts), MatchVerdict::NoMatch);
+        assert_eq!(classify_match(&a, &[], &opts), MatchVerdict::NoMatch);
+    }
+
+    #[test]
+    fn fft_matches_exhaustive() {
+        let a = pseudo_random(1, 1000);
+        let mut b = a[130..900].to_vec();
+        b.iter_mut().step_by(4).for_each(|sub_fp| *sub_fp ^= 0xf0f0);
+        let c = pseudo_random(2, 300);
+
+        for (a, b) in [(&a, &b), (&b, &a), (&a, &c), (&c, &b), (&a, &a)] {
+            for max_offset in [0, 120, 200, 2000] {
+                let opts = CompareOptions { max_offset, ..CompareOptions::default() };
+                assert_eq!(match_fft(a, b, &opts), compare_fingerprints(a, b, &opts), "{max_offset}");
+            }
+        }
+
+        let score = match_fft(&a, &b, &CompareOptions { max_offset: 200, ..CompareOptions::default() }).unwrap();
+        assert_eq!(score.offset, 130);
+        assert_eq!(match_fft(&a, &[], &CompareOptions::default()), None);
+    }
+
+    #[test]
+    fn trimmed_edges() {
+        let a = pseudo_random(1, 300);
+        let mut b = a[20..].to_vec();
+        b[..5].copy_from_slice(&[0; 5]);
+        let last = b.len() - 1;
+        b[last] = !b[last];
+
+        let opts = CompareOptions { trim_edges: 5, ..CompareOptions::default() };
+        let score = compare_fingerprints(&a, &b, &opts).unwrap();
+        assert_eq!((score.offset, score.overlap, score.bit_errors), (20, 270, 0));
+        assert_ne!(compare_fingerprints(&a, &b, &CompareOptions::default()).unwrap().bit_errors, 0);
+
+        let (compressed_a, compressed_b) = (compress_fingerprint(&a, 1), compress_fingerprint(&b, 1));
+        assert_eq!(compare_compressed(&compressed_a, &compressed_b, &opts), Ok(Some(score.clone())));
+        assert_eq!(match_fft(&a, &b, &opts), Some(score.clone()));
+        assert_eq!(compare_detailed(&a, &b, &opts).unwrap().summary().worst_window_bit_error_rate, 0.0);
+
+        let opts = CompareOptions { trim_edges: 150, ..CompareOptions::default() };
+        assert_eq!(compare_fingerprints(&a, &b, &opts), None);
+        assert_eq!(compare_compressed(&compressed_a, &compressed_b, &opts), Ok(None));
+    }
+
+    #[test]
+    fn symmetric_comparison() {
+        let opts = CompareOptions::default();
+        let a = pseudo_random(1, 200);
+        let b = a[7..150].to_vec();
+
+        let forward = compare_symmetric(&a, &b, &opts).unwrap();
+        let backward = compare_symmetric(&b, &a, &opts).unwrap();
+        assert_eq!(forward, compare_fingerprints(&a, &b, &opts).unwrap());
+        assert_eq!(forward.offset, 7);
+        assert_eq!(backward.offset, -7);
+        assert_eq!((backward.overlap, backward.bit_errors), (forward.overlap, forward.bit_errors));
+
+        // Both offsets overlap by a single identical item, so they tie.
+        let (a, b) = ([1, 2, 3], [3, 4, 1]);
+        let forward = compare_symmetric(&a, &b, &opts).unwrap();
+        let backward = compare_symmetric(&b, &a, &opts).unwrap();
+        assert_eq!(forward.offset, -backward.offset);
+        assert_eq!(forward.bit_errors, 0);
+        let unordered = (compare_fingerprints(&a, &b, &opts).unwrap(), compare_fingerprints(&b, &a, &opts).unwrap());
+        assert_eq!(unordered.0.offset, unordered.1.offset);
+
+        assert_eq!(compare_symmetric(&a, &[], &opts), None);
+    }
+
+    #[test]
+    fn aligned_parts() {
+        let a = [1, 2, 3, 4, 5];
+        let b = [3, 4, 5, 6];
+        assert_eq!(aligned_overlap(&a, &b, 2), (&a[2..], &b[..3]));
+        assert_eq!(aligned_overlap(&b, &a, -2), (&b[..3], &a[2..]));
+        assert_eq!(aligned_overlap(&a, &b, 0), (&a[..4], &b[..]));
+        assert_eq!(aligned_overlap(&a, &b, 5), (&a[5..], &b[..0]));
+        assert_eq!(aligned_overlap(&a, &b, -10), (&a[..0], &b[4..]));
+
+        let score = compare_fingerprints(&a, &b, &CompareOptions::default()).unwrap();
+        let (a, b) = aligned_overlap(&a, &b, score.offset);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn ranking() {
+        let query = pseudo_random(1, 200);
+        let noisy = query.iter().map(|sub_fp| sub_fp ^ 0b11).collect::<Vec<_>>();
+        let unrelated = pseudo_random(2, 200);
+        let candidates = [&unrelated[..], &[], &query[5..], &noisy];
+
+        let ranked = rank_matches(&query, &candidates, &CompareOptions::default());
+        let indices = ranked.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(indices, [2, 3, 0]);
+        assert_eq!(ranked[0].1.offset, 5);
+        assert_eq!(ranked[0].1.bit_errors, 0);
+    }
+
+    #[test]
+    fn inverted_distance() {
+        let a = pseudo_random(1, 100);
+        let b = pseudo_random(2, 100);
+        let inverted_b = b.iter().map(|sub_fp| !sub_fp).collect::<Vec<_>>();
+
+        assert_eq!(fingerprint_distance_inverted(&a, &b), fingerprint_distance(&a, &inverted_b));
+        assert_eq!(fingerprint_distance_inverted(&a, &b), 3200 - fingerprint_distance(&a, &b));
+        assert_eq!(fingerprint_distance_inverted(&b, &inverted_b), 0);
+    }
+
+    #[test]
+    fn containment() {
+        let long = pseudo_random(1, 2400);
+        let clip = long[1000..1080].to_vec();
+        assert_eq_float!(containment_score(&clip, &long, 2000, 1), 1.0);
+        assert_eq_float!(containment_score(&long[..80], &long, 0, 1), 1.0);
+
+        let noisy = clip.iter().map(|sub_fp| sub_fp ^ 1).collect::<Vec<_>>();
+        assert_eq_float!(containment_score(&noisy, &long, 2000, 1), 1.0 - 1.0 / 32.0);
+
+        // Placements beyond the maximal offset are not considered.
+        assert!(containment_score(&clip, &long, 500, 1) < 0.7);
+        assert!(containment_score(&pseudo_random(2, 80), &long, 2000, 1) < 0.7);
+        assert_eq_float!(containment_score(&long, &clip, 2000, 1), 0.0);
+        assert_eq_float!(containment_score(&[], &long, 2000, 1), 0.0);
+
+        assert_eq_float!(containment_score(&long[50..53], &long, 100, 1), 1.0);
+        assert_eq_float!(containment_score(&long[50..53], &long, 100, 10), 0.0);
+    }
+
+    #[test]
+    fn no_overlap() {
+        assert_eq!(compare_fingerprints(&[], &[1, 2, 3], &CompareOptions::default()), None);
+    }
+
+    #[test]
+    fn algorithm_mismatch() {
+        let a = Fingerprint::new(1, pseudo_random(1, 100)).unwrap();
+        let b = Fingerprint::new(3, pseudo_random(1, 100)).unwrap();
+
+        assert_eq!(compare_checked(&a, &b), Err(CompareError::AlgorithmMismatch(1, 3)));
+        assert_eq!(compare_checked(&a, &a).unwrap().bit_errors, 0);
+    }
+
+    #[test]
+    fn detailed_comparison_of_partial_match() {
+        let common = pseudo_random(1, 200);
+        let a = [&common[..], &pseudo_random(2, 200)].concat();
+        let b = [&common[..], &pseudo_random(3, 200)].concat();
+
+        let detailed = compare_detailed(&a, &b, &CompareOptions::default()).unwrap();
+        assert_eq!(detailed.score.offset, 0);
+
+        let windows = detailed.window_bit_error_rates;
+        assert_eq!(windows.len(), 4);
+        assert_eq_float!(windows[0], 0.0);
+        assert_eq_float!(windows[1], 0.0);
+        assert_eq_float!(windows[2], 0.5, 0.05);
+        assert_eq_float!(windows[3], 0.5, 0.05);
+    }
+
+    #[test]
+    fn summary_of_partial_match() {
+        let common = pseudo_random(1, 300);
+        let a = [&common[..], &pseudo_random(2, 100)].concat();
+        let b = [&common[..], &pseudo_random(3, 100)].concat();
+
+        let summary = compare_detailed(&a, &b, &CompareOptions::default()).unwrap().summary();
+        assert_eq!(summary.best_offset, 0);
+        assert_eq_float!(summary.best_bit_error_rate, 0.125, 0.02);
+        assert_eq_float!(summary.worst_window_bit_error_rate, 0.5, 0.05);
+        assert_eq_float!(summary.mean_window_bit_error_rate, 0.125, 0.02);
+        assert_eq_float!(summary.median_window_bit_error_rate, 0.0);
+    }
+
+    #[test]
+    fn acoustid_scores() {
+        let a = pseudo_random(1, 300);
+        assert_eq_float!(acoustid_score(&a, &a), 1.0);
+        assert_eq_float!(acoustid_score(&a, &a[10..]), 1.0);
+        assert_eq_float!(acoustid_score(&a[10..], &a), 1.0);
+        assert_eq_float!(acoustid_score(&a, &pseudo_random(2, 300)), 0.0);
+        assert_eq_float!(acoustid_score(&a, &[]), 0.0);
+
+        // Every subfingerprint differs in one of its lowest bits: 1 - 2 * 1 / 32.
+        let noisy = a.iter().map(|sub_fp| sub_fp ^ 1).collect::<Vec<_>>();
+        assert_eq_float!(acoustid_score(&a, &noisy), 0.9375);
+
+        // Only half of the shorter fingerprint overlaps with the longer one.
+        let half = [&a[..150], &pseudo_random(3, 150)].concat();
+        assert_eq_float!(acoustid_score(&a, &half), 0.5, 0.05);
+
+        // Few distinct values are penalized.
+        let repetitive = [1u32 << 31, 1 << 30].repeat(100);
+        let noisy = repetitive.iter().map(|sub_fp| sub_fp ^ 1).collect::<Vec<_>>();
+        assert!(acoustid_score(&repetitive, &noisy) < 0.9375);
+    }
+
+    #[test]
+    fn acoustid_scores_regression() {
+        // Pins the current scores of a real matching pair, so that changes to the scoring show up.
+        // They were not checked against `acoustid_compare2(a, b, 80)` of a running pg_acoustid.
+        let (a, b) = matching_pair();
+        assert_eq_float!(acoustid_score(&a, &b), 0.7872159);
+        assert_eq_float!(acoustid_score(&b, &a), 0.7872159);
+        assert_eq_float!(acoustid_score(&a[..120], &b), 0.8104166);
+        assert_eq_float!(acoustid_score(&a, &b[40..]), 0.7697917);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_serial() {
+        use crate::comparison::compare_fingerprints_parallel;
+
+        let a = pseudo_random(1, 3000);
+        let opts = CompareOptions { max_offset: 2000, ..CompareOptions::default() };
+        for b in [a[700..].to_vec(), pseudo_random(2, 2500), [1, 2, 3].repeat(100), vec![]] {
+            assert_eq!(compare_fingerprints_parallel(&a, &b, &opts), compare_fingerprints(&a, &b, &opts));
+            assert_eq!(compare_fingerprints_parallel(&b, &a, &opts), compare_fingerprints(&b, &a, &opts));
+        }
+
+        let unbounded = CompareOptions { max_offset: usize::MAX, ..CompareOptions::default() };
+        let b = &a[700..];
+        assert_eq!(compare_fingerprints_parallel(&a, b, &unbounded), compare_fingerprints(&a, b, &opts));
+    }
+
+    #[test]
+    fn compressed_comparison() {
+        let a = pseudo_random(1, 300);
+        let mut b = a[40..].to_vec();
+        b.iter_mut().step_by(3).for_each(|sub_fp| *sub_fp ^= 0b1011);
+        let c = pseudo_random(2, 100);
+        let d = a[..5].to_vec();
+
+        for (a, b) in [(&a, &b), (&b, &a), (&a, &c), (&c, &b), (&a, &d), (&d, &a)] {
+            let (compressed_a, compressed_b) = (compress_fingerprint(a, 1), compress_fingerprint(b, 1));
+            for max_offset in [0, 10, 50, 500, 1 << 40, usize::MAX] {
+                let opts = CompareOptions { max_offset, ..CompareOptions::default() };
+                let expected = compare_fingerprints(a, b, &opts);
+                assert_eq!(compare_compressed(&compressed_a, &compressed_b, &opts), Ok(expected));
+            }
+        }
+
+        let opts = CompareOptions::default();
+        let empty = compress_fingerprint(&[], 1);
+        assert_eq!(compare_compressed(&empty, &compress_fingerprint(&a, 1), &opts), Ok(None));
+        assert_eq!(compare_compressed(&empty[..3], &empty, &opts), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn reader_comparison() {
+        use std::io::{Cursor, ErrorKind};
+
+        let a = pseudo_random(1, 300);
+        let b = a[25..200].to_vec();
+        let (compressed_a, compressed_b) = (compress_fingerprint(&a, 1), compress_fingerprint(&b, 1));
+
+        let opts = CompareOptions::default();
+        let score = compare_readers(Cursor::new(&compressed_a), Cursor::new(&compressed_b), &opts).unwrap();
+        assert_eq!(score, compare_fingerprints(&a, &b, &opts));
+        assert_eq!(score.unwrap().offset, 25);
+
+        let unbounded = CompareOptions { max_offset: usize::MAX, ..CompareOptions::default() };
+        let short = compress_fingerprint(&[1, 2, 3], 1);
+        let score = compare_readers(Cursor::new(&short), Cursor::new(&short), &unbounded).unwrap().unwrap();
+        assert_eq!((score.offset, score.overlap, score.bit_errors), (0, 3, 0));
+
+        let opts = CompareOptions { trim_edges: 10, ..CompareOptions::default() };
+        let score = compare_readers(Cursor::new(&compressed_a), Cursor::new(&compressed_b), &opts).unwrap();
+        assert_eq!(Ok(score), compare_compressed(&compressed_a, &compressed_b, &opts));
+
+        let truncated = &compressed_b[..compressed_b.len() - 1];
+        let err = compare_readers(Cursor::new(&compressed_a), Cursor::new(truncated), &opts).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let trailing = [&compressed_b[..], &[0]].concat();
+        let err = compare_readers(Cursor::new(&compressed_a), Cursor::new(trailing), &opts).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reader_errors() {
+        use std::io::{Cursor, ErrorKind, Read};
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken"))
+            }
+        }
+
+        let compressed = compress_fingerprint(&pseudo_random(1, 100), 1);
+        let opts = CompareOptions::default();
+        let broken = Cursor::new(&compressed[..20]).chain(Broken);
+        let err = compare_readers(Cursor::new(&compressed), broken, &opts).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(err.to_string(), "broken");
+    }
+
+    #[test]
+    fn edits() {
+        let a = pseudo_random(1, 300);
+        let b = [&a[..100], &a[150..]].concat();
+        assert_eq!(edit_distance(&a, &b, 0), 50);
+        assert_eq!(edit_distance(&b, &a, 0), 50);
+        assert_eq!(edit_distance(&a, &a, 0), 0);
+
+        // Small differences are tolerated, larger ones make every item of `noisy` a replacement.
+        let noisy = b.iter().map(|sub_fp| sub_fp ^ 0b101).collect::<Vec<_>>();
+        assert_eq!(edit_distance(&a, &noisy, 2), 50);
+        assert_eq!(edit_distance(&a, &noisy, 1), 300);
+
+        let mut replaced = a.clone();
+        replaced[10..20].copy_from_slice(&pseudo_random(2, 10));
+        let edited = [&replaced[..200], &[0; 3], &replaced[200..]].concat();
+        assert_eq!(edit_distance(&a, &edited, 0), 13);
+
+        assert_eq!(edit_distance(&a, &[], 0), 300);
+        assert_eq!(edit_distance(&[], &a[..5], 0), 5);
+        assert_eq!(edit_distance(&[], &[], 0), 0);
+    }
+
+    #[test]
+    fn agreement_per_bit() {
+        let a = pseudo_random(1, 200);
+        assert_eq!(bit_agreement(&a, &a, 0), [1.0; 32]);
+        assert_eq!(bit_agreement(&a, &a[10..], 10), [1.0; 32]);
+
+        // Bit 3 differs in every other item and bit 31 in all of them.
+        let b = a.iter()
+            .enumerate()
+            .map(|(i, sub_fp)| sub_fp ^ (1 << 31) ^ (((i % 2) as u32) << 3))
+            .collect::<Vec<_>>();
+        let agreement = bit_agreement(&a, &b, 0);
+        assert_eq!(agreement[3], 0.5);
+        assert_eq!(agreement[31], 0.0);
+        assert!(agreement.iter().enumerate().all(|(p, &rate)| p == 3 || p == 31 || rate == 1.0));
+
+        assert_eq!(bit_agreement(&a, &[], 0), [0.0; 32]);
+    }
+
+    #[test]
+    fn pairs_of_shifted_copy() {
+        let a = pseudo_random(1, 50);
+        let mut b = a[7..].to_vec();
+        b[10] ^= 0b111;
+        b[20] = !b[20];
+
+        let pairs = matching_pairs(&a, &b, 7, 0);
+        assert_eq!(pairs.len(), 41);
+        assert!(pairs.iter().all(|&(i, j)| i == j + 7));
+        assert!(!pairs.contains(&(17, 10)) && !pairs.contains(&(27, 20)));
+
+        assert_eq!(matching_pairs(&a, &b, 7, 3).len(), 42);
+        let swapped = matching_pairs(&a, &b, 7, 3).into_iter().map(|(i, j)| (j, i)).collect::<Vec<_>>();
+        assert_eq!(matching_pairs(&b, &a, -7, 3), swapped);
+        assert!(matching_pairs(&a, &b, 100, 32).is_empty());
+    }
+
+    #[test]
+    fn jaccard() {
+        let a = pseudo_random(1, 100);
+        let mut reversed = a.clone();
+        reversed.reverse();
+        assert_eq_float!(jaccard_similarity(&a, &a), 1.0);
+        assert_eq_float!(jaccard_similarity(&a, &reversed), 1.0);
+        assert_eq_float!(jaccard_similarity(&a, &pseudo_random(2, 100)), 0.0);
+
+        assert_eq_float!(jaccard_similarity(&[1, 2, 2, 3], &[2, 3, 4]), 0.5);
+        assert_eq_float!(jaccard_similarity(&a, &[]), 0.0);
+        assert_eq_float!(jaccard_similarity(&[], &[]), 0.0);
+    }
+
+    #[test]
+    fn coarse_to_fine() {
+        // Flipping a single bit at a time changes the fingerprint gradually, like real audio does.
+        let a = pseudo_random(3, 1000)
+            .into_iter()
+            .scan(0u32, |sub_fp, r| {
+                *sub_fp ^= 1 << (r % 32);
+                Some(*sub_fp)
+            })
+            .collect::<Vec<_>>();
+
+        for shift in [0, 1, 37, 119] {
+            let b = a[shift..].to_vec();
+            let opts = CompareOptions { max_offset: 120, ..CompareOptions::default() };
+            let exhaustive = compare_fingerprints(&a, &b, &opts);
+            assert_eq!(exhaustive.as_ref().unwrap().offset, shift as isize);
+            for step in [0, 1, 4, 8, 16] {
+                assert_eq!(match_coarse_to_fine(&a, &b, 120, step, 1), exhaustive, "shift {shift}, step {step}");
+                assert_eq!(match_coarse_to_fine(&b, &a, 120, step, 1).unwrap().offset, -(shift as isize));
+            }
+        }
+
+        assert_eq!(match_coarse_to_fine(&a, &[], 120, 8, 1), None);
+        assert_eq!(match_coarse_to_fine(&a, &a, 0, 8, 1).unwrap().offset, 0);
+
+        // The only matching alignment overlaps by 3 items.
+        let mut b = a[a.len() - 3..].to_vec();
+        b.extend(pseudo_random(2, 50));
+        let score = match_coarse_to_fine(&a, &b, 1000, 1, 1).unwrap();
+        assert_eq!((score.overlap, score.bit_errors), (3, 0));
+        let score = match_coarse_to_fine(&a, &b, 1000, 1, 10).unwrap();
+        assert!(score.overlap >= 10 && score.bit_errors > 0, "{score:?}");
+    }
+
+    #[test]
+    fn merging_chunks() {
+        let full = pseudo_random(1, 300);
+        let (a, b) = (&full[..200], &full[150..]);
+        assert_eq!(merge_overlapping(a, b, 100, 1), Some(full.clone()));
+        assert_eq!(merge_overlapping(a, b, 50, 1), Some(full.clone()));
+        // The overlap starts before the last 40 items of `a`.
+        assert_eq!(merge_overlapping(a, b, 40, 1), None);
+
+        // Small differences in the overlap are tolerated, the items of `a` are kept.
+        let mut noisy = b.to_vec();
+        noisy.iter_mut().take(50).for_each(|sub_fp| *sub_fp ^= 0b1111);
+        assert_eq!(merge_overlapping(a, &noisy, 100, 1), Some(full.clone()));
+
+        assert_eq!(merge_overlapping(a, &full[120..180], 100, 1), Some(a.to_vec()));
+        assert_eq!(merge_overlapping(a, &pseudo_random(2, 100), 100, 1), None);
+        assert_eq!(merge_overlapping(a, &[], 100, 1), None);
+        assert_eq!(merge_overlapping(&[], b, 100, 1), None);
+
+        // The chunks overlap by 3 items only.
+        assert_eq!(merge_overlapping(a, &full[197..], 100, 1), Some(full.clone()));
+        assert_eq!(merge_overlapping(a, &full[197..], 100, 10), None);
+    }
+
+    #[test]
+    fn minimal_overlap() {
+        let a = pseudo_random(1, 100);
+        let mut b = a[97..].to_vec();
+        b.extend(pseudo_random(2, 50));
+
+        // The only matching alignment overlaps by 3 items.
+        let opts = CompareOptions { max_offset: 120, ..CompareOptions::default() };
+        let score = compare_fingerprints(&a, &b, &opts).unwrap();
+        assert_eq!((score.offset, score.overlap, score.bit_errors), (97, 3, 0));
+
+        let opts = CompareOptions { min_overlap: 10, ..opts };
+        let score = compare_fingerprints(&a, &b, &opts).unwrap();
+        assert!(score.overlap >= 10 && score.bit_errors > 0, "{score:?}");
+        let (compressed_a, compressed_b) = (compress_fingerprint(&a, 1), compress_fingerprint(&b, 1));
+        assert_eq!(compare_compressed(&compressed_a, &compressed_b, &opts), Ok(Some(score)));
+
+        let (a, b) = (&a[..3], &a[..3]);
+        assert_eq!(compare_fingerprints(a, b, &opts), None);
+        assert!(compare_detailed(a, b, &opts).is_none());
+        assert!(rank_matches(a, &[b], &opts).is_empty());
+        assert_eq!(compare_compressed(&compress_fingerprint(a, 1), &compress_fingerprint(b, 1), &opts), Ok(None));
+    }
+
+    #[test]
+    fn drifting_match() {
+        let a = pseudo_random(1, 2000);
+        // Every 500th item of `a` is skipped, as if `b` was played 0.2% faster.
+        let b = (0..1990).map(|j| a[j + (j + 250) / 500]).collect::<Vec<_>>();
+
+        let fixed = compare_fingerprints(&a, &b, &CompareOptions { max_offset: 10, ..Default::default() }).unwrap();
+        let drifting = match_with_drift(&a, &b, 10, 5000, 1).unwrap();
+        assert!(drifting.drift_ppm > 0);
+        assert!(drifting.score.similarity() > 0.95);
+        assert!(drifting.score.similarity() > fixed.similarity() + 0.2);
+
+        let same = match_with_drift(&a, &a[3..], 10, 5000, 1).unwrap();
+        assert_eq!(same.drift_ppm, 0);
+        assert_eq!(same.score.offset, 3);
+        assert_eq!(same.score.bit_errors, 0);
+
+        assert_eq!(match_with_drift(&a, &b, 10, 0, 1).unwrap().score, fixed);
+        assert_eq!(match_with_drift(&a, &[], 10, 5000, 1), None);
+
+        // The only matching alignment overlaps by 3 items.
+        let mut b = a[a.len() - 3..].to_vec();
+        b.extend(pseudo_random(2, 50));
+        let score = match_with_drift(&a, &b, 2000, 5000, 1).unwrap().score;
+        assert_eq!((score.overlap, score.bit_errors), (3, 0));
+        let score = match_with_drift(&a, &b, 2000, 5000, 10).unwrap().score;
+        assert!(score.overlap >= 10 && score.bit_errors > 0, "{score:?}");
+    }
+}
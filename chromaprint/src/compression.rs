@@ -0,0 +1,1790 @@
+//! Compression of fingerprints into the compact format used by fpcalc and AcoustID.
+//!
+//! Every subfingerprint is XORed with its predecessor and the positions of set bits
+//! in the result are stored as differences ("spans") between consecutive bit indices:
+//!
+//! ```text
+//! value:  0b0100_0110
+//!            │    ││
+//!            │    │└─ bit 2, span = 2 - 0 = 2
+//!            │    └── bit 3, span = 3 - 2 = 1
+//!            └─────── bit 7, span = 7 - 3 = 4
+//! spans:  [2, 1, 4, 0]
+//! ```
+//!
+//! A span of 0 terminates a subfingerprint. Spans are packed as 3-bit values, so those
+//! of 7 or more are stored as 7 with the remainder moved to a separate stream of 5-bit
+//! extensions, which follows the spans. The whole blob is prefixed with a 4-byte header
+//! consisting of the algorithm id and the 24-bit big-endian number of subfingerprints.
+
+use std::fmt::{Display, Formatter};
+
+const HEADER_SIZE: usize = 4;
+/// Largest number of subfingerprints the 24-bit length of the header can hold.
+const MAX_LENGTH: usize = 0xFF_FFFF;
+const MAX_NORMAL_SPAN: u8 = 0b111;
+const MAX_EXTENSION: u8 = 0b1_1111;
+/// Width of subfingerprints produced by all the Chromaprint algorithms.
+const SUBFINGERPRINT_BITS: u32 = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompressError {
+    /// Input ended before all the declared subfingerprints could be decoded.
+    UnexpectedEndOfData,
+    /// Spans of a subfingerprint point past its last bit.
+    InvalidSpan,
+    /// Extension of a span with the given value points past the last bit of a subfingerprint.
+    InvalidExtension(u8),
+    /// Input continues after all the declared subfingerprints, which usually means
+    /// that the header declares too few of them.
+    TrailingData,
+    /// Output buffer cannot hold all the declared subfingerprints.
+    BufferTooSmall { needed: usize, got: usize },
+}
+
+impl Display for DecompressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::UnexpectedEndOfData => write!(f, "Unexpected end of compressed data"),
+            DecompressError::InvalidSpan => write!(f, "Invalid span in compressed data"),
+            DecompressError::InvalidExtension(value) => write!(f, "Invalid span extension {value} in compressed data"),
+            DecompressError::TrailingData => write!(f, "Unexpected data after the end of compressed data"),
+            DecompressError::BufferTooSmall { needed, got } => {
+                write!(f, "Buffer of {got} items is too small for {needed} subfingerprints")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+impl From<DecompressError> for std::io::Error {
+    fn from(err: DecompressError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompressError {
+    /// Subfingerprint at the given index does not fit in 32 bits.
+    ValueOutOfRange { index: usize },
+    /// Fingerprint has more subfingerprints than the 24-bit length of the header can hold.
+    TooLong { length: usize },
+}
+
+impl Display for CompressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressError::ValueOutOfRange { index } => write!(f, "Subfingerprint #{index} does not fit in 32 bits"),
+            CompressError::TooLong { length } => {
+                write!(f, "Fingerprint of {length} subfingerprints does not fit in the header")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompressError {}
+
+/// Compresses a fingerprint calculated with the given algorithm.
+///
+/// Spans are computed from the values of subfingerprints, independently of their byte order
+/// in memory. Stored fingerprints can be read with [crate::fingerprint_from_le_bytes]
+/// or [crate::fingerprint_from_be_bytes].
+///
+/// Panics if the fingerprint has more than 16,777,215 subfingerprints, which the header
+/// cannot hold, and so do all the other functions compressing a fingerprint without returning
+/// a [CompressError].
+#[must_use]
+pub fn compress_fingerprint(fp: &[u32], algorithm: u8) -> Vec<u8> {
+    compress_fingerprint_width::<SUBFINGERPRINT_BITS>(fp, algorithm)
+}
+
+/// Compresses a fingerprint whose subfingerprints are only `BITS` wide.
+///
+/// The format stays the same, only the spans never point past bit `BITS`.
+/// Bits above `BITS` are ignored. `BITS` must be between 1 and 32.
+#[must_use]
+pub fn compress_fingerprint_width<const BITS: u32>(fp: &[u32], algorithm: u8) -> Vec<u8> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "compress_fingerprint",
+        input_len = fp.len(),
+        output_len = tracing::field::Empty,
+        span_count = tracing::field::Empty,
+        extension_count = tracing::field::Empty,
+    )
+    .entered();
+
+    let (spans, exts) = split_spans::<BITS>(fp.iter().copied());
+    let compressed = pack_fingerprint(&spans, &exts, algorithm, fp.len());
+
+    #[cfg(feature = "tracing")]
+    span.record("output_len", compressed.len())
+        .record("span_count", spans.len())
+        .record("extension_count", exts.len());
+    compressed
+}
+
+/// Compresses an experimental fingerprint of 24-bit subfingerprints, ignoring their upper 8 bits.
+///
+/// This is [compress_fingerprint_width] fixed at 24 bits. Fingerprints calculated by Chromaprint
+/// use all 32 bits, so those should be compressed with [compress_fingerprint] instead.
+#[must_use]
+pub fn compress_fingerprint_24(fp: &[u32], algorithm: u8) -> Vec<u8> {
+    compress_fingerprint_width::<24>(fp, algorithm)
+}
+
+/// Options of [compress_fingerprint_opts] and [decompress_fingerprint_opts].
+#[derive(Debug, Clone)]
+pub struct CompressOptions {
+    /// Whether every subfingerprint is XORed with its predecessor before computing the spans.
+    ///
+    /// Without it the output is not compatible with `fpcalc` and can only be decompressed
+    /// with the same option, but it shows how much the XOR stage contributes to compression.
+    pub xor_delta: bool,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self { xor_delta: true }
+    }
+}
+
+/// Compresses a fingerprint like [compress_fingerprint], with the stages configured by `opts`.
+#[must_use]
+pub fn compress_fingerprint_opts(fp: &[u32], algorithm: u8, opts: &CompressOptions) -> Vec<u8> {
+    if opts.xor_delta {
+        return compress_fingerprint(fp, algorithm);
+    }
+    // Spans are always computed from XOR deltas, so cumulative XORs leave the subfingerprints as they are.
+    let (spans, exts) = split_spans::<SUBFINGERPRINT_BITS>(cumulative_xors(fp.iter().copied()));
+    pack_fingerprint(&spans, &exts, algorithm, fp.len())
+}
+
+/// Joins several fingerprints of the same algorithm into one.
+pub fn concat_fingerprints(parts: &[&[u32]]) -> Vec<u32> {
+    parts.concat()
+}
+
+/// Compresses several fingerprints of the same algorithm as a single one.
+///
+/// Subfingerprints are XORed across the boundaries of the parts, so the result is the same
+/// as compressing [concat_fingerprints] of the parts and decompresses to their flat concatenation.
+#[must_use]
+pub fn compress_concat(parts: &[&[u32]], algorithm: u8) -> Vec<u8> {
+    let length = parts.iter().map(|part| part.len()).sum();
+    let (spans, exts) = split_spans::<SUBFINGERPRINT_BITS>(parts.iter().flat_map(|part| part.iter().copied()));
+    pack_fingerprint(&spans, &exts, algorithm, length)
+}
+
+/// Computes spans and extensions of a fingerprint, exactly as they are stored before packing.
+///
+/// Spans are 3-bit values, where 7 means that the actual span continues
+/// in the next 5-bit extension.
+#[must_use]
+pub fn fingerprint_spans(fp: &[u32]) -> (Vec<u8>, Vec<u8>) {
+    split_spans::<SUBFINGERPRINT_BITS>(fp.iter().copied())
+}
+
+/// XORs every subfingerprint with its predecessor, the first one is XORed with 0.
+///
+/// These are the values the compressor turns into spans: every set bit of a delta
+/// becomes one span, followed by a terminating span per subfingerprint.
+pub fn deltas(fp: &[u32]) -> impl Iterator<Item = u32> + '_ {
+    xor_deltas(fp.iter().copied())
+}
+
+/// Compresses a fingerprint whose subfingerprints are stored in a different integer type.
+///
+/// Values of wider types must fit in 32 bits, otherwise an error pointing at the first
+/// offending subfingerprint is returned. Fingerprints too long for the header are an error too.
+pub fn compress_fingerprint_typed<T: Copy + Into<u64>>(fp: &[T], algorithm: u8) -> Result<Vec<u8>, CompressError> {
+    if let Some(index) = fp.iter().position(|&sub_fp| sub_fp.into() > u64::from(u32::MAX)) {
+        return Err(CompressError::ValueOutOfRange { index });
+    }
+    if fp.len() > MAX_LENGTH {
+        return Err(CompressError::TooLong { length: fp.len() });
+    }
+
+    let (spans, exts) = split_spans::<SUBFINGERPRINT_BITS>(fp.iter().map(|&sub_fp| sub_fp.into() as u32));
+    Ok(pack_fingerprint(&spans, &exts, algorithm, fp.len()))
+}
+
+/// Compresses a fingerprint whose subfingerprints are stored as signed integers
+/// with the same bit patterns, as some bindings and databases do.
+#[must_use]
+pub fn compress_fingerprint_i32(fp: &[i32], algorithm: u8) -> Vec<u8> {
+    let (spans, exts) = split_spans::<SUBFINGERPRINT_BITS>(fp.iter().map(|&sub_fp| sub_fp as u32));
+    pack_fingerprint(&spans, &exts, algorithm, fp.len())
+}
+
+fn split_spans<const BITS: u32>(fp: impl IntoIterator<Item = u32>) -> (Vec<u8>, Vec<u8>) {
+    let mut spans = Vec::new();
+    let mut exts = Vec::new();
+    for_each_span::<BITS, _>(fp, |span| {
+        if span >= MAX_NORMAL_SPAN {
+            spans.push(MAX_NORMAL_SPAN);
+            exts.push(span - MAX_NORMAL_SPAN);
+        } else {
+            spans.push(span);
+        }
+    });
+    (spans, exts)
+}
+
+/// Packs spans and extensions of a fingerprint of `length` subfingerprints into a compressed blob.
+///
+/// This is the counterpart of [fingerprint_spans]. The streams are not validated,
+/// only their lowest 3 and 5 bits are stored, but a `length` too large for the header
+/// is an error. Together they split compression into computing the spans and packing them,
+/// so spans can be altered in between:
+///
+/// ```
+/// use rusty_chromaprint::{compress_fingerprint, decompress_fingerprint, fingerprint_spans, pack_spans};
+///
+/// let fp = [0b1011, 0b0110, 0b0111];
+/// let (spans, exts) = fingerprint_spans(&fp);
+/// assert_eq!(spans, [1, 1, 2, 0, 1, 2, 1, 0, 1, 0]);
+/// assert_eq!(pack_spans(&spans, &exts, 1, fp.len()), Ok(compress_fingerprint(&fp, 1)));
+///
+/// // Spans encode XOR deltas, so clearing the lowest bit of the first delta flips it in all the items.
+/// let compressed = pack_spans(&[2, 2, 0, 1, 2, 1, 0, 1, 0], &exts, 1, fp.len()).unwrap();
+/// assert_eq!(decompress_fingerprint(&compressed), Ok((1, vec![0b1010, 0b0111, 0b0110])));
+/// ```
+pub fn pack_spans(spans: &[u8], extensions: &[u8], algorithm: u8, length: usize) -> Result<Vec<u8>, CompressError> {
+    if length > MAX_LENGTH {
+        return Err(CompressError::TooLong { length });
+    }
+    Ok(pack_fingerprint(spans, extensions, algorithm, length))
+}
+
+fn pack_fingerprint(spans: &[u8], extensions: &[u8], algorithm: u8, length: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(HEADER_SIZE + pack3_size(spans.len()) + pack5_size(extensions.len()));
+    output.extend_from_slice(&header(algorithm, length));
+    pack3(spans, &mut output);
+    pack5(extensions, &mut output);
+    output
+}
+
+/// Compresses a fingerprint like [compress_fingerprint], passing the compressed bytes to `emit`
+/// one by one instead of allocating them.
+///
+/// Extensions are stored after all the spans, so the spans are computed twice.
+pub(crate) fn compress_fingerprint_with<F: FnMut(u8)>(fp: &[u32], algorithm: u8, mut emit: F) {
+    header(algorithm, fp.len()).into_iter().for_each(&mut emit);
+
+    let mut spans = BitStream::<3>::default();
+    for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+        spans.write(span.min(MAX_NORMAL_SPAN), &mut emit);
+    });
+    spans.finish(&mut emit);
+
+    let mut exts = BitStream::<5>::default();
+    for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+        if span >= MAX_NORMAL_SPAN {
+            exts.write(span - MAX_NORMAL_SPAN, &mut emit);
+        }
+    });
+    exts.finish(&mut emit);
+}
+
+/// Compresses a fingerprint like [compress_fingerprint], but with exactly one allocation
+/// of the exact output size.
+///
+/// Spans are computed twice: first to find out the size of the output and then to fill it,
+/// which trades some speed for a deterministic memory usage.
+#[must_use]
+pub fn compress_fingerprint_exact(fp: &[u32], algorithm: u8) -> Vec<u8> {
+    let mut span_count = 0;
+    let mut ext_count = 0;
+    for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+        span_count += 1;
+        if span >= MAX_NORMAL_SPAN {
+            ext_count += 1;
+        }
+    });
+
+    let spans_size = pack3_size(span_count);
+    let mut output = vec![0; HEADER_SIZE + spans_size + pack5_size(ext_count)];
+    output[..HEADER_SIZE].copy_from_slice(&header(algorithm, fp.len()));
+
+    let (spans, exts) = output[HEADER_SIZE..].split_at_mut(spans_size);
+    let mut spans = BitWriter::<3>::new(spans);
+    let mut exts = BitWriter::<5>::new(exts);
+    for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+        if span >= MAX_NORMAL_SPAN {
+            spans.write(MAX_NORMAL_SPAN);
+            exts.write(span - MAX_NORMAL_SPAN);
+        } else {
+            spans.write(span);
+        }
+    });
+    output
+}
+
+/// Summary of how well a fingerprint compresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionReport {
+    /// Size of the uncompressed subfingerprints in bytes.
+    pub raw_bytes: usize,
+
+    /// Size of the compressed fingerprint in bytes, including the header.
+    pub compressed_bytes: usize,
+
+    /// Ratio of [CompressionReport::raw_bytes] to [CompressionReport::compressed_bytes].
+    pub ratio: f32,
+
+    /// Number of 3-bit spans, including the terminating ones.
+    pub span_count: usize,
+
+    /// Number of 5-bit extensions.
+    pub extension_count: usize,
+}
+
+/// Computes how well a fingerprint compresses without building the compressed output.
+pub fn compression_report(fp: &[u32]) -> CompressionReport {
+    let mut span_count = 0;
+    let mut extension_count = 0;
+    for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+        span_count += 1;
+        if span >= MAX_NORMAL_SPAN {
+            extension_count += 1;
+        }
+    });
+
+    let raw_bytes = fp.len() * 4;
+    let compressed_bytes = HEADER_SIZE + pack3_size(span_count) + pack5_size(extension_count);
+    CompressionReport {
+        raw_bytes,
+        compressed_bytes,
+        ratio: raw_bytes as f32 / compressed_bytes as f32,
+        span_count,
+        extension_count,
+    }
+}
+
+/// Size in bytes of the compressed fingerprint, including the header, without building it.
+pub fn compressed_size(fp: &[u32]) -> usize {
+    compression_report(fp).compressed_bytes
+}
+
+/// Ratio of the uncompressed size of a fingerprint to its compressed size.
+///
+/// Unlike [compress_fingerprint] this takes no algorithm, because its id always occupies
+/// a single byte of the header and does not change the ratio.
+pub fn compression_ratio(fp: &[u32]) -> f32 {
+    compression_report(fp).ratio
+}
+
+/// Differences between the spans of two fingerprints, see [span_diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanDiff {
+    /// Index of the first span that differs in the streams of spans, `None` if they are equal.
+    pub first_span: Option<usize>,
+
+    /// Indices of the subfingerprints whose spans differ, including those present in one fingerprint only.
+    pub subfingerprints: Vec<usize>,
+
+    /// Number of spans of both fingerprints, including the terminating ones.
+    pub span_counts: (usize, usize),
+}
+
+/// Compares the spans that both fingerprints compress into.
+///
+/// Spans are computed from XOR deltas, so changing a single subfingerprint changes the spans
+/// of both it and the subfingerprint after it. Spans are compared before being split into
+/// extensions, so a difference in an extension is reported at its span.
+pub fn span_diff(a: &[u32], b: &[u32]) -> SpanDiff {
+    let spans_of = |fp: &[u32]| {
+        let mut spans = vec![Vec::new()];
+        for_each_span::<SUBFINGERPRINT_BITS, _>(fp.iter().copied(), |span| {
+            spans.last_mut().unwrap().push(span);
+            if span == 0 {
+                spans.push(Vec::new());
+            }
+        });
+        spans.pop();
+        spans
+    };
+    let (a, b) = (spans_of(a), spans_of(b));
+
+    let span_counts: (usize, usize) = (a.iter().map(Vec::len).sum(), b.iter().map(Vec::len).sum());
+    // When one stream is a prefix of the other, they diverge where the shorter one ends.
+    let first_span = a.iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .position(|(a, b)| a != b)
+        .or_else(|| (span_counts.0 != span_counts.1).then(|| usize::min(span_counts.0, span_counts.1)));
+    SpanDiff {
+        first_span,
+        subfingerprints: (0..usize::max(a.len(), b.len())).filter(|&i| a.get(i) != b.get(i)).collect(),
+        span_counts,
+    }
+}
+
+/// Calls `emit` with every span of the fingerprint, including the terminating zeros.
+///
+/// Spans are not split into normal values and extensions yet, so they can be up to `BITS`.
+fn for_each_span<const BITS: u32, F: FnMut(u8)>(fp: impl IntoIterator<Item = u32>, mut emit: F) {
+    const { assert!(BITS >= 1 && BITS <= 32, "subfingerprints must be 1 to 32 bits wide") };
+    let mask = u32::MAX >> (32 - BITS);
+    for mut precompressed_fp in xor_deltas(fp.into_iter().map(|sub_fp| sub_fp & mask)) {
+        // Bits are numbered from 1, so the span of the lowest bit is 1.
+        let mut last_bit_index = 0;
+        while precompressed_fp != 0 {
+            let bit_index = precompressed_fp.trailing_zeros() + 1;
+            let span = (bit_index - last_bit_index) as u8;
+            // Spans of up to 32-bit values always fit in a normal span plus a 5-bit extension.
+            debug_assert!(span <= MAX_NORMAL_SPAN + MAX_EXTENSION);
+            emit(span);
+            last_bit_index = bit_index;
+            // Clears the lowest set bit.
+            precompressed_fp &= precompressed_fp - 1;
+        }
+        emit(0);
+    }
+}
+
+fn header(algorithm: u8, length: usize) -> [u8; HEADER_SIZE] {
+    assert!(length <= MAX_LENGTH, "fingerprint of {length} subfingerprints does not fit in the header");
+    [algorithm, (length >> 16) as u8, (length >> 8) as u8, length as u8]
+}
+
+/// XORs every subfingerprint with its predecessor, the first one is XORed with 0.
+pub(crate) fn xor_deltas(fp: impl IntoIterator<Item = u32>) -> impl Iterator<Item = u32> {
+    fp.into_iter().scan(0, |last_sub_fp, sub_fp| {
+        let delta = sub_fp ^ *last_sub_fp;
+        *last_sub_fp = sub_fp;
+        Some(delta)
+    })
+}
+
+/// Inverse of [xor_deltas], XORs every subfingerprint with all of its predecessors.
+fn cumulative_xors(fp: impl IntoIterator<Item = u32>) -> impl Iterator<Item = u32> {
+    fp.into_iter().scan(0, |last_sub_fp, sub_fp| {
+        *last_sub_fp ^= sub_fp;
+        Some(*last_sub_fp)
+    })
+}
+
+/// Changes the algorithm id in the header of a compressed fingerprint.
+///
+/// The rest of the fingerprint is copied without being decompressed, so it is not validated.
+pub fn relabel_algorithm(compressed: &[u8], new_algorithm: u8) -> Result<Vec<u8>, DecompressError> {
+    read_header(compressed)?;
+    let mut relabeled = compressed.to_vec();
+    relabeled[0] = new_algorithm;
+    Ok(relabeled)
+}
+
+/// Returns the number of subfingerprints declared in the header of a compressed fingerprint.
+///
+/// Only the header is read, so the rest of the data is not validated.
+#[cfg_attr(no_panic, no_panic::no_panic)]
+pub fn estimate_length(compressed: &[u8]) -> Result<usize, DecompressError> {
+    read_header(compressed).map(|(_, length)| length)
+}
+
+/// Header of a compressed fingerprint, see [split_regions].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintHeader {
+    /// Id of the algorithm used to calculate the fingerprint.
+    pub algorithm: u8,
+
+    /// Number of subfingerprints in the fingerprint.
+    pub length: usize,
+}
+
+/// Splits a compressed fingerprint into its header and the packed bytes of its spans and extensions.
+///
+/// The spans are still checked to find where the extensions start, but no subfingerprints
+/// are reconstructed. Together with the 4 bytes of the header, both regions make up
+/// the whole input.
+#[cfg_attr(no_panic, no_panic::no_panic)]
+pub fn split_regions(compressed: &[u8]) -> Result<(FingerprintHeader, &[u8], &[u8]), DecompressError> {
+    let (algorithm, length) = read_header(compressed)?;
+    let spans = unpack_iter::<3>(tail(compressed, HEADER_SIZE)?.iter().copied());
+    let (span_count, ext_count) = count_spans(spans, length)?;
+
+    let ext_offset = HEADER_SIZE + pack3_size(span_count);
+    check_size(compressed.len(), ext_offset + pack5_size(ext_count))?;
+    let spans = compressed.get(HEADER_SIZE..ext_offset).ok_or(DecompressError::UnexpectedEndOfData)?;
+    let header = FingerprintHeader { algorithm, length };
+    Ok((header, spans, tail(compressed, ext_offset)?))
+}
+
+/// Checks whether a compressed fingerprint would decompress successfully, without
+/// allocating the subfingerprints.
+///
+/// Checking stops at the first problem, which [decompress_fingerprint] would report as an error.
+#[cfg_attr(no_panic, no_panic::no_panic)]
+pub fn is_valid_fingerprint(compressed: &[u8]) -> bool {
+    decompress_iter(compressed).is_ok_and(|(_, mut items)| items.all(|item| item.is_ok()))
+}
+
+/// Estimate of the work needed to decompress a fingerprint, see [decode_cost].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeCost {
+    /// Number of subfingerprints declared in the header.
+    pub subfingerprint_count: usize,
+
+    /// Upper bound of the number of spans, assuming that all the data after the header are spans.
+    pub max_span_count: usize,
+}
+
+/// Estimates how much work decompressing a fingerprint takes, for scheduling batches.
+///
+/// Only the header and the size of the data are looked at, so it is as cheap as
+/// [estimate_length] and similarly does not validate the data.
+#[cfg_attr(no_panic, no_panic::no_panic)]
+pub fn decode_cost(compressed: &[u8]) -> Result<DecodeCost, DecompressError> {
+    let (_, subfingerprint_count) = read_header(compressed)?;
+    Ok(DecodeCost {
+        subfingerprint_count,
+        max_span_count: max_length(compressed.len()),
+    })
+}
+
+/// Numbers of items found while decompressing a fingerprint, see [decompress_fingerprint_with_stats].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Number of 3-bit spans, including the terminating ones.
+    pub span_count: usize,
+
+    /// Number of 5-bit extensions.
+    pub extension_count: usize,
+
+    /// Number of decompressed subfingerprints.
+    pub subfingerprint_count: usize,
+}
+
+/// Decompresses a fingerprint, returning the id of its algorithm and its subfingerprints.
+pub fn decompress_fingerprint(compressed: &[u8]) -> Result<(u8, Vec<u32>), DecompressError> {
+    let (_, length) = read_bounded_header(compressed)?;
+    let mut fp = Vec::with_capacity(length);
+    let algorithm = decompress_fingerprint_with(compressed, |sub_fp| fp.push(sub_fp))?;
+    Ok((algorithm, fp))
+}
+
+/// Decompresses a fingerprint, passing each of its subfingerprints to `sink` in order
+/// instead of collecting them. Returns the id of the algorithm.
+///
+/// If an error is returned, `sink` might have already received some of the subfingerprints.
+pub fn decompress_fingerprint_with<F: FnMut(u32)>(compressed: &[u8], sink: F) -> Result<u8, DecompressError> {
+    let (algorithm, _) = decompress_width_with::<SUBFINGERPRINT_BITS, F>(compressed, sink)?;
+    Ok(algorithm)
+}
+
+/// Decompresses a fingerprint like [decompress_fingerprint], also returning how many
+/// spans and extensions it was stored with.
+///
+/// The counts are the same as in the [CompressionReport] of the decompressed fingerprint,
+/// which helps to predict the size of compressing it again.
+pub fn decompress_fingerprint_with_stats(compressed: &[u8]) -> Result<(u8, Vec<u32>, DecodeStats), DecompressError> {
+    let (_, length) = read_bounded_header(compressed)?;
+    let mut fp = Vec::with_capacity(length);
+    let (algorithm, stats) = decompress_width_with::<SUBFINGERPRINT_BITS, _>(compressed, |sub_fp| fp.push(sub_fp))?;
+    Ok((algorithm, fp, stats))
+}
+
+/// Decompresses a fingerprint into a preallocated buffer without allocating, returning the id
+/// of its algorithm.
+///
+/// The buffer has to hold at least [estimate_length] items, only that many are written.
+/// If an error is returned, some of them might have been written already.
+#[cfg_attr(no_panic, no_panic::no_panic)]
+pub fn decompress_into_slice(compressed: &[u8], out: &mut [u32]) -> Result<u8, DecompressError> {
+    let (_, length) = read_bounded_header(compressed)?;
+    if out.len() < length {
+        return Err(DecompressError::BufferTooSmall { needed: length, got: out.len() });
+    }
+
+    let (algorithm, fp) = decompress_iter(compressed)?;
+    for (item, sub_fp) in out.iter_mut().zip(fp) {
+        *item = sub_fp?;
+    }
+    Ok(algorithm)
+}
+
+/// Decompresses a fingerprint compressed by [compress_fingerprint_opts] with the same options.
+pub fn decompress_fingerprint_opts(
+    compressed: &[u8],
+    opts: &CompressOptions,
+) -> Result<(u8, Vec<u32>), DecompressError> {
+    let (algorithm, fp) = decompress_fingerprint(compressed)?;
+    if opts.xor_delta {
+        return Ok((algorithm, fp));
+    }
+    Ok((algorithm, xor_deltas(fp).collect()))
+}
+
+/// Decompresses a fingerprint compressed by [compress_fingerprint_width] with the same `BITS`.
+///
+/// Spans pointing past bit `BITS` are reported as [DecompressError::InvalidSpan]
+/// or [DecompressError::InvalidExtension].
+pub fn decompress_fingerprint_width<const BITS: u32>(compressed: &[u8]) -> Result<(u8, Vec<u32>), DecompressError> {
+    let (_, length) = read_bounded_header(compressed)?;
+    let mut fp = Vec::with_capacity(length);
+    let (algorithm, _) = decompress_width_with::<BITS, _>(compressed, |sub_fp| fp.push(sub_fp))?;
+    Ok((algorithm, fp))
+}
+
+/// Decompresses a fingerprint compressed by [compress_fingerprint_24].
+///
+/// This is [decompress_fingerprint_width] fixed at 24 bits, so spans pointing past bit 24 are errors.
+pub fn decompress_fingerprint_24(compressed: &[u8]) -> Result<(u8, Vec<u32>), DecompressError> {
+    decompress_fingerprint_width::<24>(compressed)
+}
+
+fn decompress_width_with<const BITS: u32, F: FnMut(u32)>(
+    compressed: &[u8],
+    sink: F,
+) -> Result<(u8, DecodeStats), DecompressError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "decompress_fingerprint",
+        input_len = compressed.len(),
+        output_len = tracing::field::Empty,
+        span_count = tracing::field::Empty,
+        extension_count = tracing::field::Empty,
+    )
+    .entered();
+
+    let (algorithm, length) = read_header(compressed)?;
+    let spans = unpack3(tail(compressed, HEADER_SIZE)?);
+    let (span_count, ext_count) = count_spans(spans.iter().copied(), length)?;
+
+    let ext_offset = HEADER_SIZE + pack3_size(span_count);
+    check_size(compressed.len(), ext_offset + pack5_size(ext_count))?;
+    let exts = unpack5(tail(compressed, ext_offset)?);
+
+    reconstruct::<BITS, _>(spans.iter().copied().take(span_count), exts.iter().copied(), sink)?;
+
+    // Only successfully decompressed fingerprints report what they decompressed to.
+    #[cfg(feature = "tracing")]
+    span.record("output_len", length)
+        .record("span_count", span_count)
+        .record("extension_count", ext_count);
+
+    let stats = DecodeStats { span_count, extension_count: ext_count, subfingerprint_count: length };
+    Ok((algorithm, stats))
+}
+
+/// Decompresses the fingerprint at the start of `data`, which may be followed by other data.
+///
+/// The header tells how many subfingerprints the fingerprint has, which is enough to find
+/// where it ends. Returns the id of its algorithm, its subfingerprints and the number of bytes
+/// it occupies, so the next item can be read right after it.
+pub fn decompress_fingerprint_consuming(data: &[u8]) -> Result<(u8, Vec<u32>, usize), DecompressError> {
+    let (_, length) = read_header(data)?;
+    let spans = unpack_iter::<3>(tail(data, HEADER_SIZE)?.iter().copied());
+    let (span_count, ext_count) = count_spans(spans, length)?;
+    let size = HEADER_SIZE + pack3_size(span_count) + pack5_size(ext_count);
+    let compressed = data.get(..size).ok_or(DecompressError::UnexpectedEndOfData)?;
+    let (algorithm, fp) = decompress_fingerprint(compressed)?;
+    Ok((algorithm, fp, size))
+}
+
+/// Decompresses fingerprints compressed one after another into a single buffer.
+///
+/// Every fingerprint is read with [decompress_fingerprint_consuming] right after the previous one.
+/// Decoding continues until the buffer is exhausted, so an empty buffer holds no fingerprints.
+pub fn decompress_all(data: &[u8]) -> Result<Vec<(u8, Vec<u32>)>, DecompressError> {
+    let mut fingerprints = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let (algorithm, fp, size) = decompress_fingerprint_consuming(&data[offset..])?;
+        fingerprints.push((algorithm, fp));
+        offset += size;
+    }
+    Ok(fingerprints)
+}
+
+/// Validates the layout of a compressed fingerprint and returns the id of its algorithm
+/// together with an iterator decompressing its subfingerprints on demand.
+///
+/// Nothing is buffered, so fingerprints can be processed without holding all of their items.
+pub(crate) fn decompress_iter(
+    compressed: &[u8],
+) -> Result<(u8, impl Iterator<Item = Result<u32, DecompressError>> + '_), DecompressError> {
+    let (algorithm, length) = read_header(compressed)?;
+    let spans = tail(compressed, HEADER_SIZE)?;
+    let (span_count, ext_count) = count_spans(unpack_iter::<3>(spans.iter().copied()), length)?;
+
+    let ext_offset = HEADER_SIZE + pack3_size(span_count);
+    check_size(compressed.len(), ext_offset + pack5_size(ext_count))?;
+
+    let spans = unpack_iter::<3>(spans.iter().copied()).take(span_count);
+    let exts = unpack_iter::<5>(tail(compressed, ext_offset)?.iter().copied());
+    Ok((algorithm, subfingerprints::<SUBFINGERPRINT_BITS>(spans, exts)))
+}
+
+/// Decompresses a fingerprint like [decompress_fingerprint], but reads it from an iterator.
+///
+/// Extensions follow all the spans, so the spans are buffered until the extensions
+/// are reached, while the extensions are consumed as they are read.
+pub fn decompress_from_bytes<I: IntoIterator<Item = u8>>(bytes: I) -> Result<(u8, Vec<u32>), DecompressError> {
+    let mut bytes = bytes.into_iter();
+    let (algorithm, length) = read_stream_header(&mut bytes)?;
+    let spans = read_stream_spans(&mut bytes, length)?;
+
+    let mut fp = Vec::with_capacity(length);
+    reconstruct::<SUBFINGERPRINT_BITS, _>(spans, unpack_iter::<5>(&mut bytes), |sub_fp| fp.push(sub_fp))?;
+    if bytes.next().is_some() {
+        return Err(DecompressError::TrailingData);
+    }
+    Ok((algorithm, fp))
+}
+
+/// Reads a compressed fingerprint from a stream of bytes and returns the id of its algorithm,
+/// its number of subfingerprints and an iterator decompressing them on demand.
+///
+/// Only the spans are buffered, the extensions are read as the subfingerprints are decompressed.
+/// No bytes are read after the last extension, so trailing data can be checked afterwards.
+pub(crate) fn decompress_stream_iter<I: Iterator<Item = u8>>(
+    mut bytes: I,
+) -> Result<(u8, usize, impl Iterator<Item = Result<u32, DecompressError>>), DecompressError> {
+    let (algorithm, length) = read_stream_header(&mut bytes)?;
+    let spans = read_stream_spans(&mut bytes, length)?;
+    Ok((algorithm, length, subfingerprints::<SUBFINGERPRINT_BITS>(spans, unpack_iter::<5>(bytes))))
+}
+
+/// Decompresses a fingerprint of `size` bytes that is not stored in a single slice.
+///
+/// `bytes_from` must return the bytes of the compressed fingerprint starting at the given offset.
+/// It is called once for the header, twice for the spans and once for the extensions,
+/// so nothing but the output needs to be buffered.
+pub(crate) fn decompress_stream<I, F>(
+    size: usize,
+    bytes_from: impl Fn(usize) -> I,
+    sink: F,
+) -> Result<u8, DecompressError>
+where
+    I: Iterator<Item = u8>,
+    F: FnMut(u32),
+{
+    let (algorithm, length) = read_stream_header(bytes_from(0))?;
+    let (span_count, ext_count) = count_spans(unpack_iter::<3>(bytes_from(HEADER_SIZE)), length)?;
+
+    let ext_offset = HEADER_SIZE + pack3_size(span_count);
+    check_size(size, ext_offset + pack5_size(ext_count))?;
+
+    let spans = unpack_iter::<3>(bytes_from(HEADER_SIZE)).take(span_count);
+    let exts = unpack_iter::<5>(bytes_from(ext_offset));
+    reconstruct::<SUBFINGERPRINT_BITS, _>(spans, exts, sink)?;
+    Ok(algorithm)
+}
+
+/// Decompresses as many subfingerprints as possible from a damaged fingerprint.
+///
+/// Returns the id of the algorithm, all the subfingerprints decoded before hitting
+/// invalid or missing data, and the error that stopped decoding, if any.
+/// If all the subfingerprints are decoded, but the input continues after them,
+/// the error is [DecompressError::TrailingData].
+/// The algorithm id is 0 when even the header is missing.
+#[must_use]
+pub fn decompress_fingerprint_lossy(compressed: &[u8]) -> (u8, Vec<u32>, Option<DecompressError>) {
+    let mut fp = Vec::new();
+    let (algorithm, length) = match read_header(compressed) {
+        Ok(header) => header,
+        Err(err) => return (0, fp, Some(err)),
+    };
+
+    let spans = unpack3(tail(compressed, HEADER_SIZE).unwrap_or_default());
+    let (spans, exts, error) = match count_spans(spans.iter().copied(), length) {
+        Ok((span_count, ext_count)) => {
+            let ext_offset = HEADER_SIZE + pack3_size(span_count);
+            let exts = unpack5(tail(compressed, ext_offset).unwrap_or_default());
+            // Missing extensions are detected while reconstructing, after the complete subfingerprints.
+            let error = check_size(compressed.len(), ext_offset + pack5_size(ext_count))
+                .err()
+                .filter(|err| *err == DecompressError::TrailingData);
+            (spans.get(..span_count).unwrap_or(&spans), exts, error)
+        }
+        // Without all the spans there is no way to tell where extensions start,
+        // so only subfingerprints that do not need them can be recovered.
+        Err(err) => (&spans[..], Vec::new(), Some(err)),
+    };
+
+    let spans = spans.iter().copied();
+    let error = reconstruct::<SUBFINGERPRINT_BITS, _>(spans, exts.iter().copied(), |sub_fp| fp.push(sub_fp)).err().or(error);
+    (algorithm, fp, error)
+}
+
+/// Finds out how many spans describe the first `length` subfingerprints
+/// and how many extensions they need.
+fn count_spans(spans: impl IntoIterator<Item = u8>, length: usize) -> Result<(usize, usize), DecompressError> {
+    let (found, span_count, ext_count) = spans.into_iter()
+        .try_fold((0, 0, 0), |(found, span_count, ext_count), span| {
+            if found == length {
+                return Err((found, span_count, ext_count));
+            }
+            Ok(match span {
+                0 => (found + 1, span_count + 1, ext_count),
+                MAX_NORMAL_SPAN => (found, span_count + 1, ext_count + 1),
+                _ => (found, span_count + 1, ext_count),
+            })
+        })
+        .unwrap_or_else(|counts| counts);
+
+    if found != length {
+        return Err(DecompressError::UnexpectedEndOfData);
+    }
+    Ok((span_count, ext_count))
+}
+
+/// Rebuilds subfingerprints from spans and extensions, passing each of them to `sink`.
+fn reconstruct<const BITS: u32, F: FnMut(u32)>(
+    spans: impl IntoIterator<Item = u8>,
+    exts: impl IntoIterator<Item = u8>,
+    mut sink: F,
+) -> Result<(), DecompressError> {
+    for sub_fp in subfingerprints::<BITS>(spans, exts) {
+        sink(sub_fp?);
+    }
+    Ok(())
+}
+
+/// Rebuilds subfingerprints from spans and extensions on demand.
+///
+/// Spans after the last terminating zero are ignored. The iterator ends after the first error.
+fn subfingerprints<const BITS: u32>(
+    spans: impl IntoIterator<Item = u8>,
+    exts: impl IntoIterator<Item = u8>,
+) -> impl Iterator<Item = Result<u32, DecompressError>> {
+    let mut spans = spans.into_iter();
+    let mut exts = exts.into_iter();
+    let mut last_sub_fp = 0;
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+
+        let mut fp = 0u32;
+        let mut bit_offset = 0;
+        loop {
+            let span = spans.next()?;
+            if span == 0 {
+                // Every span below is checked as soon as it is added, so spans of a finished
+                // subfingerprint can never sum past its width.
+                debug_assert!(bit_offset <= BITS);
+                last_sub_fp ^= fp;
+                return Some(Ok(last_sub_fp));
+            }
+
+            let error = if span == MAX_NORMAL_SPAN {
+                // Every span of 7 is continued by exactly one extension.
+                match exts.next() {
+                    Some(ext) => {
+                        bit_offset += u32::from(span + ext);
+                        Some(DecompressError::InvalidExtension(ext)).filter(|_| bit_offset > BITS)
+                    }
+                    None => Some(DecompressError::UnexpectedEndOfData),
+                }
+            } else {
+                bit_offset += u32::from(span);
+                Some(DecompressError::InvalidSpan).filter(|_| bit_offset > BITS)
+            };
+            if let Some(error) = error {
+                failed = true;
+                return Some(Err(error));
+            }
+            // Every span is positive, so no bit can be set twice.
+            debug_assert_eq!(fp & (1 << (bit_offset - 1)), 0);
+            fp |= 1 << (bit_offset - 1);
+        }
+    })
+}
+
+/// Reads the algorithm id and the number of subfingerprints from the header.
+fn read_header(compressed: &[u8]) -> Result<(u8, usize), DecompressError> {
+    match compressed {
+        [algorithm, a, b, c, ..] => {
+            let length = (usize::from(*a) << 16) | (usize::from(*b) << 8) | usize::from(*c);
+            Ok((*algorithm, length))
+        }
+        _ => Err(DecompressError::UnexpectedEndOfData),
+    }
+}
+
+/// Reads the header like [read_header], but also checks that the declared number
+/// of subfingerprints can fit in the data.
+///
+/// The length can then be used to size the output safely.
+fn read_bounded_header(compressed: &[u8]) -> Result<(u8, usize), DecompressError> {
+    let (algorithm, length) = read_header(compressed)?;
+    if length > max_length(compressed.len()) {
+        return Err(DecompressError::UnexpectedEndOfData);
+    }
+    Ok((algorithm, length))
+}
+
+/// Upper bound of the number of subfingerprints in a compressed fingerprint of `size` bytes.
+///
+/// Every subfingerprint needs at least its terminating 3-bit span.
+pub(crate) fn max_length(size: usize) -> usize {
+    size.checked_sub(HEADER_SIZE).map_or(0, |span_bytes| span_bytes * 8 / 3)
+}
+
+/// Reads the header like [read_header], but from the first bytes of an iterator.
+fn read_stream_header(mut bytes: impl Iterator<Item = u8>) -> Result<(u8, usize), DecompressError> {
+    let mut header = [0; HEADER_SIZE];
+    for byte in &mut header {
+        *byte = bytes.next().ok_or(DecompressError::UnexpectedEndOfData)?;
+    }
+    read_header(&header)
+}
+
+/// Reads the spans of `length` subfingerprints that follow the header in a stream of bytes.
+///
+/// Reading stops right after the byte holding the last span, the next bytes hold the extensions.
+fn read_stream_spans(bytes: impl Iterator<Item = u8>, length: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut unpacked = unpack_iter::<3>(bytes);
+    let mut spans = Vec::new();
+    let mut found = 0;
+    while found < length {
+        let span = unpacked.next().ok_or(DecompressError::UnexpectedEndOfData)?;
+        if span == 0 {
+            found += 1;
+        }
+        spans.push(span);
+    }
+    Ok(spans)
+}
+
+/// Checks that the compressed fingerprint has exactly the size implied by its spans.
+fn check_size(size: usize, expected: usize) -> Result<(), DecompressError> {
+    match size.cmp(&expected) {
+        std::cmp::Ordering::Less => Err(DecompressError::UnexpectedEndOfData),
+        std::cmp::Ordering::Equal => Ok(()),
+        std::cmp::Ordering::Greater => Err(DecompressError::TrailingData),
+    }
+}
+
+/// Returns the bytes after `offset`, so that decoding never panics on short inputs.
+fn tail(bytes: &[u8], offset: usize) -> Result<&[u8], DecompressError> {
+    bytes.get(offset..).ok_or(DecompressError::UnexpectedEndOfData)
+}
+
+/// Number of bytes needed to pack the given number of 3-bit values.
+fn pack3_size(len: usize) -> usize {
+    (len * 3).div_ceil(8)
+}
+
+/// Number of bytes needed to pack the given number of 5-bit values.
+fn pack5_size(len: usize) -> usize {
+    (len * 5).div_ceil(8)
+}
+
+fn pack3(values: &[u8], output: &mut Vec<u8>) {
+    pack::<3>(values, output)
+}
+
+fn pack5(values: &[u8], output: &mut Vec<u8>) {
+    pack::<5>(values, output)
+}
+
+fn unpack3(bytes: &[u8]) -> Vec<u8> {
+    unpack::<3>(bytes)
+}
+
+fn unpack5(bytes: &[u8]) -> Vec<u8> {
+    unpack::<5>(bytes)
+}
+
+/// Packs `BITS`-wide values into bytes, starting from the least significant bits.
+fn pack<const BITS: u32>(values: &[u8], output: &mut Vec<u8>) {
+    let mut stream = BitStream::<BITS>::default();
+    let mut emit = |byte| output.push(byte);
+    for &value in values {
+        stream.write(value, &mut emit);
+    }
+    stream.finish(&mut emit);
+}
+
+/// Packs `BITS`-wide values like [pack], passing every byte on as soon as it is complete.
+#[derive(Default)]
+struct BitStream<const BITS: u32> {
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl<const BITS: u32> BitStream<BITS> {
+    fn write(&mut self, value: u8, emit: &mut impl FnMut(u8)) {
+        self.buffer |= u32::from(value & ((1 << BITS) - 1)) << self.buffered_bits;
+        self.buffered_bits += BITS;
+        while self.buffered_bits >= 8 {
+            emit(self.buffer as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    /// Passes on the last incomplete byte, if any.
+    fn finish(self, emit: &mut impl FnMut(u8)) {
+        if self.buffered_bits > 0 {
+            emit(self.buffer as u8);
+        }
+    }
+}
+
+/// Packs `BITS`-wide values into a preallocated and zeroed buffer.
+struct BitWriter<'a, const BITS: u32> {
+    output: &'a mut [u8],
+    bit_offset: usize,
+}
+
+impl<'a, const BITS: u32> BitWriter<'a, BITS> {
+    fn new(output: &'a mut [u8]) -> Self {
+        Self { output, bit_offset: 0 }
+    }
+
+    fn write(&mut self, value: u8) {
+        let index = self.bit_offset / 8;
+        let shifted = u16::from(value & ((1 << BITS) - 1)) << (self.bit_offset % 8);
+        self.output[index] |= shifted as u8;
+        if shifted > 0xff {
+            self.output[index + 1] |= (shifted >> 8) as u8;
+        }
+        self.bit_offset += BITS as usize;
+    }
+}
+
+/// Unpacks all the `BITS`-wide values that fit entirely in the given bytes.
+fn unpack<const BITS: u32>(bytes: &[u8]) -> Vec<u8> {
+    let mut values = Vec::with_capacity(bytes.len() * 8 / BITS as usize);
+    values.extend(unpack_iter::<BITS>(bytes.iter().copied()));
+    values
+}
+
+/// Lazily unpacks `BITS`-wide values like [unpack].
+fn unpack_iter<const BITS: u32>(bytes: impl IntoIterator<Item = u8>) -> impl Iterator<Item = u8> {
+    let mut bytes = bytes.into_iter();
+    let mut buffer = 0u32;
+    let mut buffered_bits = 0;
+    std::iter::from_fn(move || {
+        while buffered_bits < BITS {
+            buffer |= u32::from(bytes.next()?) << buffered_bits;
+            buffered_bits += 8;
+        }
+        let value = (buffer & ((1 << BITS) - 1)) as u8;
+        buffer >>= BITS;
+        buffered_bits -= BITS;
+        Some(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert_eq_float;
+    use crate::compression::{
+        compress_concat, compress_fingerprint, compress_fingerprint_24, compress_fingerprint_exact,
+        compress_fingerprint_i32, compress_fingerprint_opts, compress_fingerprint_typed, compress_fingerprint_width,
+        compressed_size, compression_ratio, compression_report, concat_fingerprints, decode_cost, decompress_all,
+        decompress_fingerprint, decompress_fingerprint_24, decompress_fingerprint_consuming,
+        decompress_fingerprint_lossy, decompress_fingerprint_opts, decompress_fingerprint_width,
+        decompress_fingerprint_with, decompress_fingerprint_with_stats, decompress_from_bytes, decompress_into_slice,
+        deltas, estimate_length, fingerprint_spans, is_valid_fingerprint, pack_spans, relabel_algorithm, span_diff,
+        split_regions, CompressError, CompressOptions, DecodeCost, DecodeStats, DecompressError, FingerprintHeader,
+        SpanDiff,
+    };
+    use crate::fingerprint::MAX_FINGERPRINT_LENGTH;
+    use crate::utils::{count_allocations, pseudo_random};
+
+    const REFERENCE: [u32; 43] = [
+        3086176501, 3077772469, 3077638581, 3052408789, 3048228821, 3046201301, 3042148311,
+        3037102035, 2969993073, 3041294129, 3045483313, 3046514967, 3050712326, 3040164098,
+        3040163847, 3073719559, 3073733965, 3212169693, 3212169693, 3220542455, 3220542399,
+        3212152503, 3077933717, 3086327509, 3080034295, 4120237047, 4119197543, 4119295527,
+        4123424293, 1975934501, 2110152245, 2111233559, 2144501255, 1005778439, 1001636359,
+        1005683463, 1005682948, 1005686104, 991003132, 991031785, 995223531, 995190635,
+        1003562858,
+    ];
+
+    #[test]
+    fn known_encodings() {
+        assert_eq!(compress_fingerprint(&[1], 0), b"\0\0\0\x01\x01");
+        assert_eq!(compress_fingerprint(&[7], 0), b"\0\0\0\x01\x49\x00");
+        assert_eq!(compress_fingerprint(&[1 << 6], 0), b"\0\0\0\x01\x07\x00");
+        assert_eq!(compress_fingerprint(&[1 << 8], 0), b"\0\0\0\x01\x07\x02");
+        assert_eq!(compress_fingerprint(&[1, 0], 0), b"\0\0\0\x02\x41\x00");
+        assert_eq!(compress_fingerprint(&[1, 1], 0), b"\0\0\0\x02\x01\x00");
+    }
+
+    #[test]
+    fn known_decodings() {
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x01").unwrap(), (0, vec![1]));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x49\x00").unwrap(), (0, vec![7]));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x07\x00").unwrap(), (0, vec![1 << 6]));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x07\x02").unwrap(), (0, vec![1 << 8]));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x02\x41\x00").unwrap(), (0, vec![1, 0]));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x02\x01\x00").unwrap(), (0, vec![1, 1]));
+    }
+
+    #[test]
+    fn round_trip() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        assert_eq!(decompress_fingerprint(&compressed).unwrap(), (1, REFERENCE.to_vec()));
+    }
+
+    #[test]
+    fn missing_data() {
+        assert_eq!(decompress_fingerprint(b"\0\0\0"), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x02\x01"), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x07"), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x3f"), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn truncated_input() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        for len in 0..compressed.len() {
+            let truncated = &compressed[..len];
+            assert_eq!(decompress_fingerprint(truncated), Err(DecompressError::UnexpectedEndOfData), "length {len}");
+            assert_eq!(decompress_fingerprint_with(truncated, |_| {}), Err(DecompressError::UnexpectedEndOfData));
+            assert!(decompress_fingerprint_lossy(truncated).2.is_some());
+        }
+    }
+
+    #[test]
+    fn garbage_input() {
+        for seed in 1..200 {
+            let garbage = pseudo_random(seed, 8).iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+            for len in 0..garbage.len() {
+                // None of these may panic, whatever they return.
+                let _ = decompress_fingerprint(&garbage[..len]);
+                let _ = decompress_fingerprint_lossy(&garbage[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let err = std::io::Error::from(DecompressError::InvalidSpan);
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid span in compressed data");
+    }
+
+    #[test]
+    fn decompress_from_iterator() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        assert_eq!(decompress_from_bytes(compressed.iter().copied()), Ok((1, REFERENCE.to_vec())));
+        assert_eq!(decompress_from_bytes(compressed.clone()), decompress_fingerprint(&compressed));
+
+        for len in 0..compressed.len() {
+            let truncated = compressed[..len].iter().copied();
+            assert_eq!(decompress_from_bytes(truncated), Err(DecompressError::UnexpectedEndOfData));
+        }
+    }
+
+    #[test]
+    fn too_few_declared_subfingerprints() {
+        let mut compressed = compress_fingerprint(&REFERENCE, 1);
+        compressed[3] = 40;
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::TrailingData));
+        // Extensions are read from the wrong place, which might be noticed first.
+        assert!(decompress_from_bytes(compressed.clone()).is_err());
+        assert!(decompress_fingerprint_lossy(&compressed).2.is_some());
+
+        let mut compressed = compress_fingerprint(&REFERENCE, 1);
+        compressed.push(0);
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::TrailingData));
+        assert_eq!(decompress_from_bytes(compressed.clone()), Err(DecompressError::TrailingData));
+        let (_, fp, error) = decompress_fingerprint_lossy(&compressed);
+        assert_eq!(fp, REFERENCE);
+        assert_eq!(error, Some(DecompressError::TrailingData));
+    }
+
+    #[test]
+    fn declared_length() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        assert_eq!(estimate_length(&compressed), Ok(43));
+        assert_eq!(estimate_length(&compressed[..4]), Ok(43));
+        assert_eq!(estimate_length(b"\x01\x01\x02\x03"), Ok(0x010203));
+        assert_eq!(estimate_length(b"\x01\0\0"), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn diff_of_spans() {
+        let mut changed = REFERENCE;
+        changed[10] ^= 1 << 20;
+        let diff = span_diff(&REFERENCE, &changed);
+        assert_eq!(diff.subfingerprints, [10, 11]);
+
+        let spans_before = fingerprint_spans(&REFERENCE[..10]).0.len();
+        let (spans, _) = fingerprint_spans(&REFERENCE);
+        assert!(diff.first_span.is_some_and(|span| span >= spans_before), "{diff:?}");
+        assert_eq!(diff.span_counts.0, spans.len());
+        assert_eq!(diff.span_counts.1, fingerprint_spans(&changed).0.len());
+
+        let same = span_diff(&REFERENCE, &REFERENCE);
+        let span_counts = (spans.len(), spans.len());
+        assert_eq!(same, SpanDiff { first_span: None, subfingerprints: vec![], span_counts });
+
+        let prefix = span_diff(&REFERENCE[..20], &REFERENCE);
+        assert_eq!(prefix.subfingerprints, (20..43).collect::<Vec<_>>());
+        assert_eq!(prefix.first_span, Some(fingerprint_spans(&REFERENCE[..20]).0.len()));
+    }
+
+    #[test]
+    fn decompress_into_buffer() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+
+        let mut out = [0; 43];
+        let (algorithm, allocations) = count_allocations(|| decompress_into_slice(&compressed, &mut out));
+        assert_eq!(algorithm, Ok(1));
+        assert_eq!(out, REFERENCE);
+        assert_eq!(allocations.count, 0);
+
+        let mut longer = [u32::MAX; 50];
+        assert_eq!(decompress_into_slice(&compressed, &mut longer), Ok(1));
+        assert_eq!(longer[..43], REFERENCE);
+        assert_eq!(longer[43..], [u32::MAX; 7]);
+
+        let mut out = [0; 42];
+        let error = DecompressError::BufferTooSmall { needed: 43, got: 42 };
+        assert_eq!(decompress_into_slice(&compressed, &mut out), Err(error));
+        let truncated = &compressed[..compressed.len() - 1];
+        assert_eq!(decompress_into_slice(truncated, &mut [0; 43]), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn decoding_cost() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let cost = decode_cost(&compressed).unwrap();
+        assert_eq!(cost.subfingerprint_count, 43);
+        let (spans, _) = fingerprint_spans(&REFERENCE);
+        assert!((spans.len()..spans.len() * 2).contains(&cost.max_span_count), "{cost:?}");
+
+        let doubled = decode_cost(&compress_fingerprint(&[REFERENCE, REFERENCE].concat(), 1)).unwrap();
+        assert_eq!(doubled.subfingerprint_count, 86);
+        assert!(doubled.max_span_count.abs_diff(cost.max_span_count * 2) <= 8, "{doubled:?}");
+
+        assert_eq!(decode_cost(&compressed[..4]), Ok(DecodeCost { subfingerprint_count: 43, max_span_count: 0 }));
+        assert_eq!(decode_cost(&compressed[..3]), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn regions() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let (header, spans, exts) = split_regions(&compressed).unwrap();
+        assert_eq!(header, FingerprintHeader { algorithm: 1, length: 43 });
+        assert_eq!([&compressed[..4], spans, exts].concat(), compressed);
+
+        let (span_values, ext_values) = fingerprint_spans(&REFERENCE);
+        assert_eq!(spans.len(), (span_values.len() * 3).div_ceil(8));
+        assert_eq!(exts.len(), (ext_values.len() * 5).div_ceil(8));
+        assert_eq!(pack_spans(&span_values, &ext_values, 1, 43).unwrap(), compressed);
+
+        let empty = compress_fingerprint(&[], 2);
+        let (header, spans, exts) = split_regions(&empty).unwrap();
+        assert_eq!(header, FingerprintHeader { algorithm: 2, length: 0 });
+        assert!(spans.is_empty() && exts.is_empty());
+
+        let truncated = &compressed[..compressed.len() - 1];
+        assert_eq!(split_regions(truncated), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(split_regions(&[compressed.clone(), vec![0]].concat()), Err(DecompressError::TrailingData));
+    }
+
+    #[test]
+    fn decoding_stats() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let (algorithm, fp, stats) = decompress_fingerprint_with_stats(&compressed).unwrap();
+        assert_eq!((algorithm, fp), (1, REFERENCE.to_vec()));
+
+        let report = compression_report(&REFERENCE);
+        let expected = DecodeStats {
+            span_count: report.span_count,
+            extension_count: report.extension_count,
+            subfingerprint_count: 43,
+        };
+        assert_eq!(stats, expected);
+
+        let (_, _, stats) = decompress_fingerprint_with_stats(&compress_fingerprint(&[], 1)).unwrap();
+        assert_eq!(stats, DecodeStats { span_count: 0, extension_count: 0, subfingerprint_count: 0 });
+        assert_eq!(decompress_fingerprint_with_stats(&compressed[..10]), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn decompress_into_callback() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+
+        let mut sum = 0u64;
+        let algorithm = decompress_fingerprint_with(&compressed, |sub_fp| sum += u64::from(sub_fp)).unwrap();
+
+        let (_, fp) = decompress_fingerprint(&compressed).unwrap();
+        assert_eq!(algorithm, 1);
+        assert_eq!(sum, fp.iter().copied().map(u64::from).sum::<u64>());
+    }
+
+    #[test]
+    fn lossy_decompression_of_truncated_data() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        for len in [compressed.len() - 1, 40, 20] {
+            let (algorithm, fp, error) = decompress_fingerprint_lossy(&compressed[..len]);
+            assert_eq!(algorithm, 1);
+            assert!(!fp.is_empty() && fp.len() < REFERENCE.len());
+            assert_eq!(fp, REFERENCE[..fp.len()]);
+            assert_eq!(error, Some(DecompressError::UnexpectedEndOfData));
+        }
+    }
+
+    #[test]
+    fn lossy_decompression_of_valid_data() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let (algorithm, fp, error) = decompress_fingerprint_lossy(&compressed);
+        assert_eq!(algorithm, 1);
+        assert_eq!(fp, REFERENCE);
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn exact_compression() {
+        for fp in [&REFERENCE[..], &pseudo_random(1, 500), &[1 << 31, 0, 1 << 8], &[]] {
+            let compressed = compress_fingerprint_exact(fp, 1);
+            assert_eq!(compressed, compress_fingerprint(fp, 1));
+            assert_eq!(compressed.capacity(), compressed.len());
+        }
+    }
+
+    #[test]
+    fn spans_and_packing() {
+        let (spans, exts) = fingerprint_spans(&[0b0100_0110, 0b0100_0110, 0b0100_0110 | 1 << 8]);
+        assert_eq!(spans, [2, 1, 4, 0, 0, 7, 0]);
+        assert_eq!(exts, [2]);
+
+        let cases: [(&[u32], u8); 4] =
+            [(&REFERENCE[..], 1), (&pseudo_random(1, 500), 2), (&[u32::MAX, 0, 1 << 31], 0), (&[], 4)];
+        for (fp, algorithm) in cases {
+            let (spans, exts) = fingerprint_spans(fp);
+            assert_eq!(pack_spans(&spans, &exts, algorithm, fp.len()), Ok(compress_fingerprint(fp, algorithm)));
+        }
+    }
+
+    #[test]
+    fn highest_bit() {
+        // The longest possible span of 32 is stored as 7 followed by an extension of 25.
+        let (spans, exts) = fingerprint_spans(&[1 << 31]);
+        assert_eq!(spans, [7, 0]);
+        assert_eq!(exts, [25]);
+
+        assert_eq!(compress_fingerprint(&[1 << 31], 0), b"\0\0\0\x01\x07\x19");
+        assert_eq!(decompress_fingerprint(b"\0\0\0\x01\x07\x19"), Ok((0, vec![1 << 31])));
+    }
+
+    #[test]
+    fn span_past_highest_bit() {
+        // Bit 32 is the last valid one, bit 33 would need to shift a u32 by 32.
+        let compressed = pack_spans(&[7, 1, 0], &[25], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidSpan));
+        let compressed = pack_spans(&[7, 7, 0], &[31, 31], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidExtension(31)));
+        let compressed = pack_spans(&[7, 0], &[26], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidExtension(26)));
+        let compressed = pack_spans(&[1, 7, 0], &[25], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidExtension(25)));
+
+        // Many short spans summing past bit 32 are caught too, before the terminating span.
+        let compressed = pack_spans(&[[4; 8].as_slice(), &[0]].concat(), &[], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Ok((0, vec![0x8888_8888])));
+        let compressed = pack_spans(&[[3; 11].as_slice(), &[0]].concat(), &[], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidSpan));
+        let compressed = pack_spans(&[[6; 5].as_slice(), &[7, 0]].concat(), &[0], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::InvalidExtension(0)));
+        assert!(!is_valid_fingerprint(&compressed));
+
+        let (_, fp, error) = decompress_fingerprint_lossy(&pack_spans(&[1, 0, 7, 1, 0], &[25], 0, 2).unwrap());
+        assert_eq!(fp, [1]);
+        assert_eq!(error, Some(DecompressError::InvalidSpan));
+    }
+
+    #[test]
+    fn validation() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        assert!(is_valid_fingerprint(&compressed));
+        assert!(is_valid_fingerprint(&compress_fingerprint(&[], 1)));
+
+        assert!(!is_valid_fingerprint(&compressed[..3]));
+        assert!(!is_valid_fingerprint(&compressed[..compressed.len() - 1]));
+        assert!(!is_valid_fingerprint(&[compressed.clone(), vec![0]].concat()));
+        let mut longer = compressed.clone();
+        longer[3] += 1;
+        assert!(!is_valid_fingerprint(&longer));
+        assert!(!is_valid_fingerprint(&pack_spans(&[7, 1, 0], &[25], 0, 1).unwrap()));
+        assert!(!is_valid_fingerprint(&pack_spans(&[7, 0], &[26], 0, 1).unwrap()));
+    }
+
+    #[test]
+    fn zero_span_inside_subfingerprint() {
+        // A span of 0 always ends a subfingerprint and every other span moves to a higher bit,
+        // so a corrupted span cannot set the same bit twice. An inserted 0 splits a subfingerprint
+        // instead and is caught by the length check.
+        assert_eq!(pack_spans(&[1, 2, 0], &[], 0, 1).unwrap(), compress_fingerprint(&[0b101], 0));
+        let compressed = pack_spans(&[1, 0, 2, 0], &[], 0, 1).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::TrailingData));
+        let compressed = pack_spans(&[1, 0, 2, 0], &[], 0, 2).unwrap();
+        assert_eq!(decompress_fingerprint(&compressed), Ok((0, vec![0b1, 0b11])));
+    }
+
+    #[test]
+    fn typed_compression() {
+        let narrow = pseudo_random(1, 100).into_iter().map(|sub_fp| sub_fp as u16).collect::<Vec<_>>();
+        let widened = narrow.iter().copied().map(u32::from).collect::<Vec<_>>();
+        assert_eq!(compress_fingerprint_typed(&narrow, 1), Ok(compress_fingerprint(&widened, 1)));
+
+        let wide = REFERENCE.iter().copied().map(u64::from).collect::<Vec<_>>();
+        assert_eq!(compress_fingerprint_typed(&wide, 1), Ok(compress_fingerprint(&REFERENCE, 1)));
+        assert_eq!(compress_fingerprint_typed(&[1u64, 1 << 32], 1), Err(CompressError::ValueOutOfRange { index: 1 }));
+    }
+
+    #[test]
+    fn too_long_for_header() {
+        let compressed = pack_spans(&[0], &[], 1, 0xFF_FFFF).unwrap();
+        assert_eq!(compressed[..4], [1, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(pack_spans(&[0], &[], 1, 1 << 24), Err(CompressError::TooLong { length: 1 << 24 }));
+        assert_eq!(pack_spans(&[0], &[], 1, usize::MAX), Err(CompressError::TooLong { length: usize::MAX }));
+        assert_eq!(
+            CompressError::TooLong { length: 1 << 24 }.to_string(),
+            "Fingerprint of 16777216 subfingerprints does not fit in the header"
+        );
+
+        let long = vec![0u8; 1 << 24];
+        assert_eq!(compress_fingerprint_typed(&long, 1), Err(CompressError::TooLong { length: 1 << 24 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "fingerprint of 16777216 subfingerprints does not fit in the header")]
+    fn compressing_too_long() {
+        let _ = compress_fingerprint(&vec![0; 1 << 24], 1);
+    }
+
+    #[test]
+    fn delta_stream() {
+        assert_eq!(deltas(&[0b1010, 0b1010, 0b0110]).collect::<Vec<_>>(), [0b1010, 0, 0b1100]);
+        assert_eq!(deltas(&[]).count(), 0);
+
+        let (spans, _) = fingerprint_spans(&REFERENCE);
+        let set_bits = deltas(&REFERENCE).map(u32::count_ones).sum::<u32>() as usize;
+        assert_eq!(set_bits + REFERENCE.len(), spans.len());
+        assert_eq!(set_bits + REFERENCE.len(), compression_report(&REFERENCE).span_count);
+    }
+
+    #[test]
+    fn spans_of_every_set_bit() {
+        // Walks the bits one at a time, as Chromaprint does.
+        fn bitwise_spans(fp: &[u32]) -> Vec<u8> {
+            let mut spans = Vec::new();
+            for mut delta in deltas(fp) {
+                let (mut bit_index, mut last_bit_index) = (1, 0);
+                while delta != 0 {
+                    if delta & 1 != 0 {
+                        spans.push(bit_index - last_bit_index);
+                        last_bit_index = bit_index;
+                    }
+                    delta >>= 1;
+                    bit_index += 1;
+                }
+                spans.push(0);
+            }
+            spans
+        }
+
+        let dense = [0, u32::MAX, 0x5555_5555, 0xAAAA_AAAA, 1 << 31, 1, 0x8000_0001];
+        for fp in [&REFERENCE[..], &pseudo_random(1, 500), &dense, &[]] {
+            let (spans, exts) = fingerprint_spans(fp);
+            let joined = spans.iter()
+                .scan(exts.iter(), |exts, &span| Some(if span == 7 { 7 + exts.next().unwrap() } else { span }))
+                .collect::<Vec<_>>();
+            assert_eq!(joined, bitwise_spans(fp));
+        }
+    }
+
+    #[test]
+    fn signed_compression() {
+        assert_eq!(compress_fingerprint_i32(&[-1, i32::MIN, 5], 1), compress_fingerprint(&[u32::MAX, 1 << 31, 5], 1));
+
+        let signed = REFERENCE.iter().map(|&sub_fp| sub_fp as i32).collect::<Vec<_>>();
+        assert!(signed.iter().any(|&sub_fp| sub_fp < 0));
+        assert_eq!(compress_fingerprint_i32(&signed, 1), compress_fingerprint(&REFERENCE, 1));
+    }
+
+    #[test]
+    fn leading_zero_subfingerprints() {
+        assert_eq!(compress_fingerprint(&[0], 1), b"\x01\0\0\x01\x00");
+        assert_eq!(compress_fingerprint(&[], 1), b"\x01\0\0\0");
+
+        for fp in [&[0][..], &[0, 0, 0, 5], &[0, 5, 0, 5], &[5, 0, 0, 0], &[0, 1 << 31, 0], &[]] {
+            let compressed = compress_fingerprint(fp, 1);
+            assert_eq!(decompress_fingerprint(&compressed), Ok((1, fp.to_vec())));
+        }
+    }
+
+    #[test]
+    fn report() {
+        let report = compression_report(&REFERENCE);
+        let (spans, exts) = fingerprint_spans(&REFERENCE);
+        assert_eq!(report.raw_bytes, 172);
+        assert_eq!(report.compressed_bytes, compress_fingerprint(&REFERENCE, 1).len());
+        assert_eq!(report.span_count, spans.len());
+        assert_eq!(report.extension_count, exts.len());
+        assert!(report.ratio > 1.2 && report.ratio < 1.5);
+        for algorithm in 0..=4 {
+            let compressed = compress_fingerprint(&REFERENCE, algorithm);
+            assert_eq_float!(compression_ratio(&REFERENCE), 172.0 / compressed.len() as f32);
+        }
+
+        assert_eq!(compression_report(&[]).ratio, 0.0);
+    }
+
+    #[test]
+    fn size_without_compressing() {
+        assert_eq!(compressed_size(&REFERENCE), compress_fingerprint(&REFERENCE, 0).len());
+        assert_eq!(compressed_size(&[]), 4);
+
+        let fp = pseudo_random(3, 500);
+        assert_eq!(compressed_size(&fp), compress_fingerprint(&fp, 0).len());
+    }
+
+    #[test]
+    fn concatenation() {
+        let a = pseudo_random(1, 100);
+        let b = pseudo_random(2, 50);
+        let joined = concat_fingerprints(&[&a, &b]);
+        assert_eq!(joined.len(), 150);
+
+        let compressed = compress_concat(&[&a, &b], 1);
+        assert_eq!(compressed, compress_fingerprint(&joined, 1));
+        assert_eq!(decompress_fingerprint(&compressed), Ok((1, [a, b].concat())));
+        assert_eq!(compress_concat(&[], 1), compress_fingerprint(&[], 1));
+    }
+
+    #[test]
+    fn single_bit_positions() {
+        for b in 0..32u8 {
+            let fp = [1u32 << b];
+            let compressed = compress_fingerprint(&fp, 1);
+            assert_eq!(decompress_fingerprint(&compressed), Ok((1, fp.to_vec())), "bit {b}");
+
+            // Spans of 7 and more continue in an extension.
+            let expected = match b + 1 {
+                span @ 1..=6 => (vec![span, 0], vec![]),
+                span => (vec![7, 0], vec![span - 7]),
+            };
+            assert_eq!(fingerprint_spans(&fp), expected, "bit {b}");
+        }
+    }
+
+    #[test]
+    fn without_xor_delta() {
+        let opts = CompressOptions { xor_delta: false };
+        let compressed = compress_fingerprint_opts(&REFERENCE, 1, &opts);
+        assert_eq!(decompress_fingerprint_opts(&compressed, &opts), Ok((1, REFERENCE.to_vec())));
+        // Consecutive subfingerprints share most of their bits, which is what the XOR stage exploits.
+        assert!(compressed.len() > compress_fingerprint(&REFERENCE, 1).len());
+
+        let compressed = compress_fingerprint_opts(&[1, 1], 1, &opts);
+        assert_eq!(compressed, pack_spans(&[1, 0, 1, 0], &[], 1, 2).unwrap());
+        assert_ne!(decompress_fingerprint(&compressed), Ok((1, vec![1, 1])));
+
+        let opts = CompressOptions::default();
+        let compressed = compress_fingerprint_opts(&REFERENCE, 1, &opts);
+        assert_eq!(compressed, compress_fingerprint(&REFERENCE, 1));
+        assert_eq!(decompress_fingerprint_opts(&compressed, &opts), Ok((1, REFERENCE.to_vec())));
+    }
+
+    #[test]
+    fn consumed_size() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let (spans, exts) = fingerprint_spans(&REFERENCE);
+        let size = 4 + (spans.len() * 3).div_ceil(8) + (exts.len() * 5).div_ceil(8);
+        assert_eq!(compressed.len(), size);
+
+        let data = [&compressed[..], b"trailing"].concat();
+        assert_eq!(decompress_fingerprint_consuming(&data), Ok((1, REFERENCE.to_vec(), size)));
+        assert_eq!(decompress_fingerprint_consuming(&compressed), Ok((1, REFERENCE.to_vec(), size)));
+        assert_eq!(decompress_fingerprint_consuming(&compress_fingerprint(&[], 2)), Ok((2, vec![], 4)));
+        let truncated = &compressed[..size - 1];
+        assert_eq!(decompress_fingerprint_consuming(truncated), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn concatenated_blobs() {
+        let other = pseudo_random(1, 100);
+        let data = [compress_fingerprint(&REFERENCE, 1), compress_fingerprint(&other, 2)].concat();
+        assert_eq!(decompress_all(&data), Ok(vec![(1, REFERENCE.to_vec()), (2, other)]));
+        assert_eq!(decompress_fingerprint(&data), Err(DecompressError::TrailingData));
+
+        let empty = compress_fingerprint(&[], 1);
+        assert_eq!(decompress_all(&[&empty[..], &empty].concat()), Ok(vec![(1, vec![]), (1, vec![])]));
+        assert_eq!(decompress_all(&[]), Ok(vec![]));
+        assert_eq!(decompress_all(&data[..data.len() - 1]), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_all(&[&data[..], &[1]].concat()), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn narrow_subfingerprints() {
+        let fp = pseudo_random(1, 100).into_iter().map(|sub_fp| sub_fp & 0xFFFF).collect::<Vec<_>>();
+        let compressed = compress_fingerprint_width::<16>(&fp, 1);
+        assert_eq!(compressed, compress_fingerprint(&fp, 1));
+        assert_eq!(decompress_fingerprint_width::<16>(&compressed), Ok((1, fp)));
+
+        assert_eq!(compress_fingerprint_width::<16>(&[0x1_0001], 1), compress_fingerprint(&[1], 1));
+        let compressed = compress_fingerprint(&[1 << 16], 1);
+        assert_eq!(decompress_fingerprint_width::<16>(&compressed), Err(DecompressError::InvalidExtension(10)));
+    }
+
+    #[test]
+    fn subfingerprints_of_24_bits() {
+        let mut fp = pseudo_random(1, 100).into_iter().map(|sub_fp| sub_fp & 0xFF_FFFF).collect::<Vec<_>>();
+        fp.extend([1 << 23, 0xFF_FFFF, 0]);
+        let compressed = compress_fingerprint_24(&fp, 1);
+        assert_eq!(compressed, compress_fingerprint(&fp, 1));
+        assert_eq!(decompress_fingerprint_24(&compressed), Ok((1, fp)));
+
+        assert_eq!(compress_fingerprint_24(&[0xFF80_0001], 1), compress_fingerprint(&[0x80_0001], 1));
+        let compressed = compress_fingerprint(&[1 << 24], 1);
+        assert_eq!(decompress_fingerprint_24(&compressed), Err(DecompressError::InvalidExtension(18)));
+    }
+
+    #[test]
+    fn relabeling() {
+        let compressed = compress_fingerprint(&REFERENCE, 1);
+        let relabeled = relabel_algorithm(&compressed, 3).unwrap();
+        assert_eq!(relabeled[0], 3);
+        assert_eq!(relabeled[1..], compressed[1..]);
+        assert_eq!(decompress_fingerprint(&relabeled), Ok((3, REFERENCE.to_vec())));
+
+        assert_eq!(relabel_algorithm(b"\x01\0\0", 3), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[test]
+    fn allocation_budget() {
+        let (compressed, allocations) = count_allocations(|| compress_fingerprint(&REFERENCE, 1));
+        assert!(allocations.count <= 12, "{allocations:?}");
+        assert!(allocations.bytes <= 1024, "{allocations:?}");
+
+        let (_, allocations) = count_allocations(|| compress_fingerprint_exact(&REFERENCE, 1));
+        assert_eq!(allocations.count, 1);
+        assert_eq!(allocations.bytes, compressed.len());
+
+        // The output, unpacked spans and unpacked extensions.
+        let (_, allocations) = count_allocations(|| decompress_fingerprint(&compressed));
+        assert!(allocations.count <= 3, "{allocations:?}");
+        assert!(allocations.bytes <= 600, "{allocations:?}");
+    }
+
+    #[test]
+    fn length_bound() {
+        // Only the header, which is exactly enough for an empty fingerprint.
+        assert_eq!(decompress_fingerprint(&[1, 0, 0, 0]), Ok((1, vec![])));
+        assert_eq!(decompress_fingerprint(&[1, 0, 0, 1]), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_fingerprint_width::<16>(&[1, 0, 0, 1]), Err(DecompressError::UnexpectedEndOfData));
+        // A byte holds at most two terminating 3-bit spans.
+        assert_eq!(decompress_fingerprint(&[1, 0, 0, 2, 0]), Ok((1, vec![0, 0])));
+        assert_eq!(decompress_fingerprint(&[1, 0, 0, 3, 0]), Err(DecompressError::UnexpectedEndOfData));
+
+        // The declared length does not cause a huge allocation before the data is checked.
+        let (decompressed, allocations) = count_allocations(|| decompress_fingerprint(&[1, 0xff, 0xff, 0xff]));
+        assert_eq!(decompressed, Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(allocations.count, 0);
+    }
+
+    #[test]
+    fn maximal_length() {
+        // Valid blob of the longest possible fingerprint, where all the subfingerprints are 0.
+        let length = MAX_FINGERPRINT_LENGTH;
+        let mut compressed = vec![1, 0xff, 0xff, 0xff];
+        compressed.resize(4 + (length * 3).div_ceil(8), 0);
+
+        let mut count = 0;
+        assert_eq!(decompress_fingerprint_with(&compressed, |sub_fp| count += usize::from(sub_fp == 0)), Ok(1));
+        assert_eq!(count, length);
+        assert_eq!(estimate_length(&compressed), Ok(length));
+
+        compressed.pop();
+        assert_eq!(decompress_fingerprint(&compressed), Err(DecompressError::UnexpectedEndOfData));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type SpanFields = (&'static str, BTreeMap<&'static str, u64>);
+
+        /// Collects numeric fields of all the spans by their ids.
+        #[derive(Clone, Default)]
+        struct Fields(Arc<Mutex<BTreeMap<usize, SpanFields>>>);
+
+        struct Visitor<'a>(&'a Fields, usize);
+
+        impl Visit for Visitor<'_> {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                self.0 .0.lock().unwrap().get_mut(&self.1).unwrap().1.insert(field.name(), value);
+            }
+
+            fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+        }
+
+        impl Subscriber for Fields {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let id = {
+                    let mut spans = self.0.lock().unwrap();
+                    let id = spans.len() + 1;
+                    spans.insert(id, (span.metadata().name(), BTreeMap::new()));
+                    id
+                };
+                span.record(&mut Visitor(self, id));
+                Id::from_u64(id as u64)
+            }
+
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                values.record(&mut Visitor(self, span.into_u64() as usize));
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let fields = Fields::default();
+        let compressed = tracing::subscriber::with_default(fields.clone(), || {
+            let compressed = compress_fingerprint(&REFERENCE, 1);
+            decompress_fingerprint(&compressed).unwrap();
+            // These fail when checking the size and when reconstructing the subfingerprints.
+            let trailing = [compressed.as_slice(), &[0]].concat();
+            assert_eq!(decompress_fingerprint(&trailing), Err(DecompressError::TrailingData));
+            let invalid = pack_spans(&[7, 1, 0], &[25], 0, 1).unwrap();
+            assert_eq!(decompress_fingerprint(&invalid), Err(DecompressError::InvalidSpan));
+            compressed
+        });
+
+        let (spans, exts) = fingerprint_spans(&REFERENCE);
+        let expected = |input_len: usize, output_len: usize| {
+            BTreeMap::from([
+                ("extension_count", exts.len() as u64),
+                ("input_len", input_len as u64),
+                ("output_len", output_len as u64),
+                ("span_count", spans.len() as u64),
+            ])
+        };
+        let recorded = fields.0.lock().unwrap().values().cloned().collect::<Vec<_>>();
+        assert_eq!(recorded, [
+            ("compress_fingerprint", expected(REFERENCE.len(), compressed.len())),
+            ("decompress_fingerprint", expected(compressed.len(), REFERENCE.len())),
+            ("decompress_fingerprint", BTreeMap::from([("input_len", compressed.len() as u64 + 1)])),
+            ("decompress_fingerprint", BTreeMap::from([("input_len", 7)])),
+        ]);
+    }
+}
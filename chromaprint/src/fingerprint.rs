@@ -0,0 +1,474 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Deref;
+
+use crate::algorithm::ALGORITHM_TEST5;
+use crate::base64::{decode_bytes, decode_fingerprint, encode_bytes, DecodeError};
+use crate::compression::{compress_fingerprint, decompress_fingerprint, DecompressError};
+
+/// Maximal number of subfingerprints that fits in the 24-bit length of the compressed header.
+pub(crate) const MAX_FINGERPRINT_LENGTH: usize = (1 << 24) - 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FingerprintError {
+    /// Algorithm id does not belong to any of the known algorithms.
+    UnknownAlgorithm(u8),
+    /// Fingerprint has more subfingerprints than can be stored in the compressed format.
+    TooLong(usize),
+}
+
+impl Display for FingerprintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FingerprintError::UnknownAlgorithm(algorithm) => write!(f, "Unknown algorithm {algorithm}"),
+            FingerprintError::TooLong(length) => {
+                write!(f, "Fingerprint of {length} items is longer than {MAX_FINGERPRINT_LENGTH}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FingerprintError {}
+
+/// Fingerprint together with the id of the algorithm that was used to calculate it.
+///
+/// It dereferences to the slice of its subfingerprints, so it can be passed
+/// to all the functions operating on `&[u32]`:
+///
+/// ```
+/// use rusty_chromaprint::{Fingerprint, ALGORITHM_DEFAULT};
+///
+/// let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+/// assert_eq!(fp.len(), 3);
+/// assert_eq!(fp[0], 1);
+///
+/// let mut sum = 0;
+/// for sub_fp in &fp {
+///     sum += sub_fp;
+/// }
+/// assert_eq!(sum, 6);
+/// ```
+///
+/// Two fingerprints are equal only if both their algorithms and subfingerprints are,
+/// while comparing with a slice or a vector looks at the subfingerprints alone:
+///
+/// ```
+/// use rusty_chromaprint::{Fingerprint, ALGORITHM_DEFAULT, ALGORITHM_TEST1};
+///
+/// let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+/// assert_eq!(fp, [1, 2, 3][..]);
+/// assert_ne!(fp, Fingerprint::new(ALGORITHM_TEST1, vec![1, 2, 3]).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    algorithm: u8,
+    data: Vec<u32>,
+}
+
+impl Fingerprint {
+    /// Creates a new [Fingerprint] from the algorithm id and its subfingerprints.
+    ///
+    /// Fails if the algorithm is unknown or if there are too many subfingerprints
+    /// to be compressed.
+    pub fn new(algorithm: u8, data: Vec<u32>) -> Result<Self, FingerprintError> {
+        validate(algorithm, data.len())?;
+        Ok(Self { algorithm, data })
+    }
+
+    /// Creates a new [Fingerprint] without validating it, for callers that already did.
+    pub fn new_unchecked(algorithm: u8, data: Vec<u32>) -> Self {
+        Self { algorithm, data }
+    }
+
+    /// Id of the algorithm used to calculate this fingerprint.
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    /// Changes the id of the algorithm, failing if it is unknown.
+    ///
+    /// Together with [Fingerprint::decompress] and [Fingerprint::compress] this relabels
+    /// a compressed fingerprint after validating it, unlike [crate::relabel_algorithm]:
+    ///
+    /// ```
+    /// use rusty_chromaprint::{Fingerprint, ALGORITHM_DEFAULT, ALGORITHM_TEST4};
+    ///
+    /// let compressed = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap().compress();
+    ///
+    /// let mut fp = Fingerprint::decompress(&compressed).unwrap();
+    /// fp.set_algorithm(ALGORITHM_TEST4).unwrap();
+    /// let relabeled = Fingerprint::decompress(&fp.compress()).unwrap();
+    /// assert_eq!(relabeled.algorithm(), ALGORITHM_TEST4);
+    /// assert_eq!(relabeled.data(), &[1, 2, 3]);
+    /// ```
+    pub fn set_algorithm(&mut self, algorithm: u8) -> Result<(), FingerprintError> {
+        validate(algorithm, self.data.len())?;
+        self.algorithm = algorithm;
+        Ok(())
+    }
+
+    /// Subfingerprints of this fingerprint.
+    pub fn data(&self) -> &[u32] {
+        &self.data
+    }
+
+    /// Key identifying the exact contents of this fingerprint, which can be used to find
+    /// duplicates in ordered collections.
+    ///
+    /// Only identical fingerprints have equal keys, use [crate::compare_fingerprints]
+    /// to find similar ones.
+    pub fn canonical_key(&self) -> (u8, &[u32]) {
+        (self.algorithm, &self.data)
+    }
+
+    /// Hash of the algorithm id and the subfingerprints, for finding exact duplicates.
+    ///
+    /// This is 64-bit FNV-1a, which is fast and stable across runs and platforms,
+    /// but not cryptographically secure. Like [Fingerprint::canonical_key] it is not
+    /// meant for fuzzy matching.
+    pub fn content_hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+
+        std::iter::once(self.algorithm)
+            .chain(self.data.iter().flat_map(|sub_fp| sub_fp.to_le_bytes()))
+            .fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+    }
+
+    /// SHA-256 of the compressed fingerprint, for use as a key in content-addressed storage.
+    ///
+    /// Unlike [Fingerprint::content_hash] it is practically free of collisions, and it is stable
+    /// across runs and platforms because the compressed format is.
+    #[cfg(feature = "sha2")]
+    pub fn content_id(&self) -> [u8; 32] {
+        use sha2::Digest;
+
+        sha2::Sha256::digest(self.compress()).into()
+    }
+
+    /// Consumes the fingerprint and returns its subfingerprints.
+    pub fn into_data(self) -> Vec<u32> {
+        self.data
+    }
+
+    /// Compresses the fingerprint, see [compress_fingerprint].
+    #[must_use]
+    pub fn compress(&self) -> Vec<u8> {
+        compress_fingerprint(&self.data, self.algorithm)
+    }
+
+    /// Decompresses a fingerprint, see [decompress_fingerprint].
+    ///
+    /// The algorithm id is taken from the header as is, without checking if it is known.
+    pub fn decompress(compressed: &[u8]) -> Result<Self, DecompressError> {
+        decompress_fingerprint(compressed).map(|(algorithm, data)| Self::new_unchecked(algorithm, data))
+    }
+}
+
+impl TryFrom<(u8, Vec<u32>)> for Fingerprint {
+    type Error = FingerprintError;
+
+    fn try_from((algorithm, data): (u8, Vec<u32>)) -> Result<Self, Self::Error> {
+        Self::new(algorithm, data)
+    }
+}
+
+impl TryFrom<&[u8]> for Fingerprint {
+    type Error = DecompressError;
+
+    fn try_from(compressed: &[u8]) -> Result<Self, Self::Error> {
+        Self::decompress(compressed)
+    }
+}
+
+impl TryFrom<&str> for Fingerprint {
+    type Error = DecodeError;
+
+    fn try_from(encoded: &str) -> Result<Self, Self::Error> {
+        // Like in `Fingerprint::decompress`, the algorithm id is taken as is.
+        decode_fingerprint(encoded).map(|(algorithm, data)| Self::new_unchecked(algorithm, data))
+    }
+}
+
+impl From<Fingerprint> for (u8, Vec<u32>) {
+    fn from(fp: Fingerprint) -> Self {
+        (fp.algorithm, fp.data)
+    }
+}
+
+impl Deref for Fingerprint {
+    type Target = [u32];
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl AsRef<[u32]> for Fingerprint {
+    fn as_ref(&self) -> &[u32] {
+        &self.data
+    }
+}
+
+impl PartialEq<[u32]> for Fingerprint {
+    fn eq(&self, other: &[u32]) -> bool {
+        self.data == other
+    }
+}
+
+impl PartialEq<&[u32]> for Fingerprint {
+    fn eq(&self, other: &&[u32]) -> bool {
+        self.data == *other
+    }
+}
+
+impl PartialEq<Vec<u32>> for Fingerprint {
+    fn eq(&self, other: &Vec<u32>) -> bool {
+        self.data == *other
+    }
+}
+
+impl<'a> IntoIterator for &'a Fingerprint {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter()
+    }
+}
+
+/// Compressed fingerprint, as produced by [compress_fingerprint].
+///
+/// Wrapping the bytes keeps them apart from arbitrary buffers, while still dereferencing
+/// to `[u8]` for the functions operating on compressed data:
+///
+/// ```
+/// use rusty_chromaprint::{encode_fingerprint, CompressedFingerprint, ALGORITHM_DEFAULT};
+///
+/// let encoded = encode_fingerprint(&[1, 2, 3], ALGORITHM_DEFAULT);
+/// let compressed = CompressedFingerprint::from_base64(&encoded).unwrap();
+/// assert_eq!(compressed[0], ALGORITHM_DEFAULT);
+/// assert_eq!(compressed.to_base64(), encoded);
+///
+/// let fp = compressed.decompress().unwrap();
+/// assert_eq!(fp.algorithm(), ALGORITHM_DEFAULT);
+/// assert_eq!(fp.data(), &[1, 2, 3]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompressedFingerprint(Vec<u8>);
+
+impl CompressedFingerprint {
+    /// Wraps bytes of a compressed fingerprint, they are only checked when decompressing.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Decodes a base64-encoded fingerprint without decompressing it.
+    pub fn from_base64(encoded: &str) -> Result<Self, DecodeError> {
+        decode_bytes(encoded).map(Self)
+    }
+
+    /// Encodes the compressed fingerprint as base64, see [crate::encode_fingerprint].
+    #[must_use]
+    pub fn to_base64(&self) -> String {
+        encode_bytes(&self.0)
+    }
+
+    /// Bytes of the compressed fingerprint.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consumes the compressed fingerprint and returns its bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Decompresses the fingerprint, see [Fingerprint::decompress].
+    pub fn decompress(&self) -> Result<Fingerprint, DecompressError> {
+        Fingerprint::decompress(&self.0)
+    }
+}
+
+impl From<Vec<u8>> for CompressedFingerprint {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&Fingerprint> for CompressedFingerprint {
+    fn from(fp: &Fingerprint) -> Self {
+        Self(fp.compress())
+    }
+}
+
+impl Deref for CompressedFingerprint {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for CompressedFingerprint {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn validate(algorithm: u8, length: usize) -> Result<(), FingerprintError> {
+    if algorithm > ALGORITHM_TEST5 {
+        return Err(FingerprintError::UnknownAlgorithm(algorithm));
+    }
+    if length > MAX_FINGERPRINT_LENGTH {
+        return Err(FingerprintError::TooLong(length));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::{ALGORITHM_DEFAULT, ALGORITHM_TEST5};
+    use crate::compression::{compress_fingerprint, decompress_fingerprint};
+    use crate::base64::{encode_fingerprint, DecodeError};
+    use crate::compression::DecompressError;
+    use crate::fingerprint::{validate, CompressedFingerprint, Fingerprint, FingerprintError, MAX_FINGERPRINT_LENGTH};
+
+    #[test]
+    fn valid_fingerprint() {
+        let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        assert_eq!(fp.algorithm(), ALGORITHM_DEFAULT);
+        assert_eq!(fp.data(), &[1, 2, 3]);
+        assert_eq!(validate(ALGORITHM_TEST5, MAX_FINGERPRINT_LENGTH), Ok(()));
+    }
+
+    #[test]
+    fn unknown_algorithm() {
+        assert_eq!(Fingerprint::new(ALGORITHM_TEST5 + 1, vec![]), Err(FingerprintError::UnknownAlgorithm(5)));
+        assert_eq!(Fingerprint::new_unchecked(ALGORITHM_TEST5 + 1, vec![]).algorithm(), 5);
+    }
+
+    #[test]
+    fn too_long() {
+        // Checking the length alone avoids allocating 64 MiB of subfingerprints.
+        assert_eq!(validate(ALGORITHM_DEFAULT, 1 << 24), Err(FingerprintError::TooLong(1 << 24)));
+    }
+
+    #[test]
+    fn changing_algorithm() {
+        let mut fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        assert_eq!(fp.set_algorithm(ALGORITHM_TEST5), Ok(()));
+        assert_eq!(fp.algorithm(), ALGORITHM_TEST5);
+
+        assert_eq!(fp.set_algorithm(ALGORITHM_TEST5 + 1), Err(FingerprintError::UnknownAlgorithm(5)));
+        assert_eq!(fp.algorithm(), ALGORITHM_TEST5);
+    }
+
+    #[test]
+    fn exact_duplicates() {
+        let a = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let b = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let c = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 4]).unwrap();
+        let d = Fingerprint::new(ALGORITHM_TEST5, vec![1, 2, 3]).unwrap();
+
+        assert_eq!(a.canonical_key(), b.canonical_key());
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert!(a.canonical_key() < c.canonical_key());
+        assert_ne!(a.content_hash(), c.content_hash());
+        assert_ne!(a.content_hash(), d.content_hash());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn content_ids() {
+        let a = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let b = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let c = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 4]).unwrap();
+        let d = Fingerprint::new(ALGORITHM_TEST5, vec![1, 2, 3]).unwrap();
+
+        assert_eq!(a.content_id(), b.content_id());
+        assert_ne!(a.content_id(), c.content_id());
+        assert_ne!(a.content_id(), d.content_id());
+
+        // Empty fingerprint compresses to just the header "\x01\0\0\0".
+        let empty = Fingerprint::new(ALGORITHM_DEFAULT, vec![]).unwrap();
+        assert_eq!(empty.content_id()[..4], [0x67, 0xab, 0xdd, 0x72]);
+    }
+
+    #[test]
+    fn equality() {
+        let a = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let b = Fingerprint::new(ALGORITHM_TEST5, vec![1, 2, 3]).unwrap();
+
+        assert_eq!(a, [1, 2, 3][..]);
+        assert_eq!(a, &[1, 2, 3][..]);
+        assert_eq!(a, vec![1, 2, 3]);
+        assert_eq!(b, vec![1, 2, 3]);
+        assert_ne!(a, vec![1, 2]);
+        assert_ne!(a, &[1, 2, 4][..]);
+
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        assert_ne!(a, Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2]).unwrap());
+    }
+
+    #[test]
+    fn hashing() {
+        use std::collections::HashSet;
+
+        let a = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let set = HashSet::from([a.clone(), a.clone(), Fingerprint::new(ALGORITHM_TEST5, vec![1, 2, 3]).unwrap()]);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&a));
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let compressed = fp.compress();
+        assert_eq!(compressed, compress_fingerprint(&[1, 2, 3], ALGORITHM_DEFAULT));
+        assert_eq!(Fingerprint::decompress(&compressed), Ok(fp.clone()));
+
+        let (algorithm, data) = fp.clone().into();
+        assert_eq!(decompress_fingerprint(&compressed), Ok((algorithm, data.clone())));
+        assert_eq!(Fingerprint::try_from((algorithm, data)), Ok(fp));
+        assert_eq!(Fingerprint::try_from((5, vec![])), Err(FingerprintError::UnknownAlgorithm(5)));
+    }
+
+    #[test]
+    fn conversions_from_compressed() {
+        let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![3086176501, 3077772469, 3077638581]).unwrap();
+        let compressed = fp.compress();
+        assert_eq!(Fingerprint::try_from(&compressed[..]), Ok(fp.clone()));
+        assert_eq!(Fingerprint::try_from(&compressed[..3]), Err(DecompressError::UnexpectedEndOfData));
+
+        let encoded = encode_fingerprint(&fp, fp.algorithm());
+        assert_eq!(Fingerprint::try_from(encoded.as_str()), Ok(fp));
+        assert_eq!(
+            Fingerprint::try_from(&encoded[..4]),
+            Err(DecodeError::Decompress(DecompressError::UnexpectedEndOfData))
+        );
+    }
+
+    #[test]
+    fn compressed_fingerprint() {
+        let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![1, 2, 3]).unwrap();
+        let compressed = CompressedFingerprint::from(&fp);
+        assert_eq!(compressed.as_bytes(), fp.compress());
+        assert_eq!(compressed.decompress(), Ok(fp.clone()));
+
+        let encoded = encode_fingerprint(&fp, fp.algorithm());
+        assert_eq!(compressed.to_base64(), encoded);
+        assert_eq!(CompressedFingerprint::from_base64(&encoded), Ok(compressed.clone()));
+        assert_eq!(CompressedFingerprint::new(compressed.clone().into_bytes()), compressed);
+    }
+
+    #[test]
+    fn invalid_compressed_fingerprint() {
+        assert_eq!(CompressedFingerprint::from_base64("AQ!A"), Err(DecodeError::InvalidChar { index: 2, byte: b'!' }));
+
+        // Base64 is decoded without checking the compressed data.
+        let truncated = CompressedFingerprint::from_base64("AQAA").unwrap();
+        assert_eq!(truncated.len(), 3);
+        assert_eq!(truncated.decompress(), Err(DecompressError::UnexpectedEndOfData));
+    }
+}
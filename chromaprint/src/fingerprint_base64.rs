@@ -0,0 +1,50 @@
+//! Serialization of a [Fingerprint] as a base64 string, in the compact form expected by AcoustID.
+//!
+//! Use it with `#[serde(with = "rusty_chromaprint::fingerprint_base64")]` on a field of type [Fingerprint].
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::base64::{decode_fingerprint, encode_fingerprint};
+use crate::fingerprint::Fingerprint;
+
+/// Serializes a fingerprint as a base64 string.
+pub fn serialize<S: Serializer>(fp: &Fingerprint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode_fingerprint(fp.data(), fp.algorithm()))
+}
+
+/// Deserializes a fingerprint from a base64 string.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fingerprint, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    let (algorithm, data) = decode_fingerprint(&encoded).map_err(D::Error::custom)?;
+    Fingerprint::new(algorithm, data).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::fingerprint::Fingerprint;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Track {
+        #[serde(with = "crate::fingerprint_base64")]
+        fingerprint: Fingerprint,
+    }
+
+    #[test]
+    fn round_trip() {
+        let track = Track { fingerprint: Fingerprint::new(1, vec![1]).unwrap() };
+        let json = serde_json::to_string(&track).unwrap();
+        assert_eq!(json, r#"{"fingerprint":"AQAAAQE"}"#);
+        assert_eq!(serde_json::from_str::<Track>(&json).unwrap(), track);
+    }
+
+    #[test]
+    fn invalid_fingerprint() {
+        assert!(serde_json::from_str::<Track>(r#"{"fingerprint":"AQ+AAQE"}"#).is_err());
+        assert!(serde_json::from_str::<Track>(r#"{"fingerprint":"AQAAAQ"}"#).is_err());
+        // Algorithm 9 is unknown.
+        assert!(serde_json::from_str::<Track>(r#"{"fingerprint":"CQAAAQE"}"#).is_err());
+    }
+}
@@ -41,12 +41,12 @@ pub fn match_fingerprints(fp1: &[u32], fp2: &[u32], _config: &Configuration) ->
     }
 
     let mut offsets = Vec::with_capacity(fp1.len() + fp2.len());
-    for i in 0..fp1.len() {
-        offsets.push((align_strip(fp1[i]) << HASH_SHIFT) | (i as u32));
+    for (i, &item) in fp1.iter().enumerate() {
+        offsets.push((align_strip(item) << HASH_SHIFT) | (i as u32));
     }
 
-    for i in 0..fp2.len() {
-        offsets.push((align_strip(fp2[i]) << HASH_SHIFT) | (i as u32) | SOURCE_MASK);
+    for (i, &item) in fp2.iter().enumerate() {
+        offsets.push((align_strip(item) << HASH_SHIFT) | (i as u32) | SOURCE_MASK);
     }
     offsets.sort_unstable();
 
@@ -61,14 +61,14 @@ pub fn match_fingerprints(fp1: &[u32], fp2: &[u32], _config: &Configuration) ->
             continue;
         }
 
-        for offset_idx2 in offset_idx + 1..offsets.len() {
-            let hash2 = offsets[offset_idx2] & HASH_MASK;
+        for &other in &offsets[offset_idx + 1..] {
+            let hash2 = other & HASH_MASK;
             if hash1 != hash2 {
                 break;
             }
 
-            let offset2 = offsets[offset_idx2] & OFFSET_MASK;
-            let source2 = offsets[offset_idx2] & SOURCE_MASK;
+            let offset2 = other & OFFSET_MASK;
+            let source2 = other & SOURCE_MASK;
             if source2 != 0 {
                 let offset_diff = offset1 as usize + fp2.len() - offset2 as usize;
                 histogram[offset_diff] += 1;
@@ -92,7 +92,8 @@ pub fn match_fingerprints(fp1: &[u32], fp2: &[u32], _config: &Configuration) ->
     best_alignments.sort_unstable_by_key(|it| Reverse(*it));
 
     let mut segments: Vec<Segment> = Vec::new();
-    for (_count, offset) in best_alignments {
+    // Only the best alignment is used.
+    if let Some(&(_count, offset)) = best_alignments.first() {
         let offset_diff = offset as isize - fp2.len() as isize;
         let offset1 = if offset_diff > 0 { offset_diff as usize } else { 0 };
         let offset2 = if offset_diff < 0 { -offset_diff as usize } else { 0 };
@@ -110,17 +111,16 @@ pub fn match_fingerprints(fp1: &[u32], fp2: &[u32], _config: &Configuration) ->
         let mut grad = Vec::with_capacity(size);
         gradient(smoothed_bit_counts.iter().copied(), &mut grad);
 
-        for i in 0..size {
-            grad[i] = grad[i].abs();
+        for g in &mut grad {
+            *g = g.abs();
         }
 
         let mut gradient_peaks = Vec::new();
         for i in 0..size {
             let gi = grad[i];
-            if i > 0 && i < size - 1 && gi > 0.15 && gi >= grad[i - 1] && gi >= grad[i + 1] {
-                if gradient_peaks.is_empty() || gradient_peaks.last().unwrap() + 1 < i {
-                    gradient_peaks.push(i);
-                }
+            if i > 0 && i < size - 1 && gi > 0.15 && gi >= grad[i - 1] && gi >= grad[i + 1]
+                && (gradient_peaks.is_empty() || gradient_peaks.last().unwrap() + 1 < i) {
+                gradient_peaks.push(i);
             }
         }
         gradient_peaks.push(size);
@@ -157,7 +157,6 @@ pub fn match_fingerprints(fp1: &[u32], fp2: &[u32], _config: &Configuration) ->
             }
             begin = end;
         }
-        break;
     }
 
     Ok(segments)
@@ -223,12 +222,12 @@ impl Segment {
 
         let new_duration = self.items_count + other.items_count;
         let new_score = (self.score * self.items_count as f64 + other.score * other.items_count as f64) / new_duration as f64;
-        return Some(Segment {
+        Some(Segment {
             offset1: self.offset1,
             offset2: self.offset2,
             items_count: new_duration,
             score: new_score,
-        });
+        })
     }
 }
 
@@ -0,0 +1,83 @@
+//! Reading of fingerprints printed by `fpcalc -json`.
+
+use std::fmt::{Display, Formatter};
+
+use crate::base64::DecodeError;
+use crate::fingerprint::CompressedFingerprint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Text is not valid JSON, with the description of the problem.
+    InvalidJson(String),
+    /// Field with the given name is missing or has an unexpected type.
+    InvalidField(&'static str),
+    /// Fingerprint field is not a valid base64-encoded fingerprint.
+    Decode(DecodeError),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidJson(err) => write!(f, "Invalid JSON: {err}"),
+            ParseError::InvalidField(name) => write!(f, "Missing or invalid field {name:?}"),
+            ParseError::Decode(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::InvalidJson(_) | ParseError::InvalidField(_) => None,
+            ParseError::Decode(err) => Some(err),
+        }
+    }
+}
+
+impl From<DecodeError> for ParseError {
+    fn from(err: DecodeError) -> Self {
+        ParseError::Decode(err)
+    }
+}
+
+/// Parses the output of `fpcalc -json`, returning the duration in seconds and the fingerprint.
+///
+/// The fingerprint is only decoded from base64, decompress it with [CompressedFingerprint::decompress].
+/// Output of `fpcalc -json -raw` holds uncompressed subfingerprints and is not supported.
+pub fn from_fpcalc_json(s: &str) -> Result<(f64, CompressedFingerprint), ParseError> {
+    let output: serde_json::Value = serde_json::from_str(s).map_err(|err| ParseError::InvalidJson(err.to_string()))?;
+    let duration = output["duration"].as_f64().ok_or(ParseError::InvalidField("duration"))?;
+    let fingerprint = output["fingerprint"].as_str().ok_or(ParseError::InvalidField("fingerprint"))?;
+    Ok((duration, CompressedFingerprint::from_base64(fingerprint)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::DecodeError;
+    use crate::fpcalc::{from_fpcalc_json, ParseError};
+
+    #[test]
+    fn fpcalc_output() {
+        let (duration, compressed) = from_fpcalc_json(r#"{"duration": 213.47, "fingerprint": "AQAAAQE"}"#).unwrap();
+        assert_eq!(duration, 213.47);
+        let fp = compressed.decompress().unwrap();
+        assert_eq!((fp.algorithm(), fp.data()), (1, &[1][..]));
+
+        let (duration, _) = from_fpcalc_json(r#"{"fingerprint": "AQAAAA", "duration": 5}"#).unwrap();
+        assert_eq!(duration, 5.0);
+    }
+
+    #[test]
+    fn invalid_output() {
+        assert!(matches!(from_fpcalc_json("{"), Err(ParseError::InvalidJson(_))));
+        assert_eq!(from_fpcalc_json(r#"{"fingerprint": "AQAAAA"}"#), Err(ParseError::InvalidField("duration")));
+        assert_eq!(
+            from_fpcalc_json(r#"{"duration": 1, "fingerprint": [1, 2]}"#),
+            Err(ParseError::InvalidField("fingerprint"))
+        );
+        assert_eq!(
+            from_fpcalc_json(r#"{"duration": 1, "fingerprint": "AQ*"}"#),
+            Err(ParseError::Decode(DecodeError::InvalidChar { index: 2, byte: b'*' }))
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Helpers for fuzzing the compression and the base64 encoding.
+//!
+//! The `round_trip` target in the `fuzz` directory checks [round_trips] on every input and,
+//! when it fails, reports the much smaller input returned by [shrink_failing]:
+//!
+//! ```text
+//! cargo +nightly fuzz run round_trip
+//! ```
+
+use crate::base64::{decode_fingerprint, encode_fingerprint};
+use crate::compression::{compress_fingerprint, compress_fingerprint_exact, decompress_fingerprint};
+
+/// Checks that a fingerprint survives compression and base64 encoding unchanged.
+///
+/// A panic in any of the stages counts as a failure.
+pub fn round_trips(fp: &[u32]) -> bool {
+    std::panic::catch_unwind(|| {
+        let compressed = compress_fingerprint(fp, 1);
+        compressed == compress_fingerprint_exact(fp, 1)
+            && decompress_fingerprint(&compressed).is_ok_and(|(algorithm, data)| algorithm == 1 && data == fp)
+            && decode_fingerprint(&encode_fingerprint(fp, 1)).is_ok_and(|(_, data)| data == fp)
+    })
+    .unwrap_or(false)
+}
+
+/// Minimizes an input for which [round_trips] fails.
+///
+/// Subfingerprints are greedily removed, in halving chunks, for as long as the round trip
+/// still fails, so the result is a reproducer from which no single subfingerprint
+/// can be removed. An input that round-trips is returned unchanged.
+pub fn shrink_failing(fp: &[u32]) -> Vec<u32> {
+    shrink(fp, |fp| !round_trips(fp))
+}
+
+fn shrink(fp: &[u32], fails: impl Fn(&[u32]) -> bool) -> Vec<u32> {
+    let mut fp = fp.to_vec();
+    if !fails(&fp) {
+        return fp;
+    }
+
+    let mut chunk = fp.len().div_ceil(2);
+    while chunk > 0 {
+        let mut start = 0;
+        while start < fp.len() {
+            let end = usize::min(start + chunk, fp.len());
+            let candidate = [&fp[..start], &fp[end..]].concat();
+            if fails(&candidate) {
+                fp = candidate;
+            } else {
+                start = end;
+            }
+        }
+        chunk /= 2;
+    }
+    fp
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzzing::{round_trips, shrink, shrink_failing};
+    use crate::utils::pseudo_random;
+
+    #[test]
+    fn passing_input() {
+        let fp = pseudo_random(1, 100);
+        assert!(round_trips(&fp));
+        assert!(round_trips(&[]));
+        assert_eq!(shrink_failing(&fp), fp);
+    }
+
+    #[test]
+    fn minimal_reproducer() {
+        let mut fp = pseudo_random(1, 100);
+        fp[17] = 7;
+        fp[80] = 9;
+
+        // Stands in for a bug triggered by a 7 followed later by a 9.
+        let fails = |fp: &[u32]| {
+            fp.iter().position(|&sub_fp| sub_fp == 7).is_some_and(|i| fp[i..].contains(&9))
+        };
+        assert_eq!(shrink(&fp, fails), [7, 9]);
+        assert_eq!(shrink(&fp, |fp| fp.len() > 10).len(), 11);
+        assert!(shrink(&fp, |_| true).is_empty());
+    }
+}
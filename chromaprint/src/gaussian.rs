@@ -10,12 +10,12 @@ pub fn gaussian_filter<'a>(mut input: &'a mut [f64], mut output: &'a mut [f64],
     let mut data2 = &mut output;
 
     for _ in 0..m {
-        box_filter(*data1, *data2, wl);
+        box_filter(data1, data2, wl);
         std::mem::swap(&mut data1, &mut data2);
     }
 
     for _ in m..n {
-        box_filter(*data1, *data2, wu);
+        box_filter(data1, data2, wu);
         std::mem::swap(&mut data1, &mut data2);
     }
 
@@ -152,7 +152,7 @@ impl ReflectIterator {
         if self.forward {
             return self.size - self.pos - 1;
         }
-        return 0;
+        0
     }
 }
 
@@ -186,7 +186,7 @@ mod tests {
     #[test]
     fn width1() {
         let input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         box_filter(&input, &mut output, 1);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(1.0, output[0]);
@@ -198,7 +198,7 @@ mod tests {
     #[test]
     fn width2() {
         let input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         box_filter(&input, &mut output, 2);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(1.0, output[0]);
@@ -209,7 +209,7 @@ mod tests {
     #[test]
     fn width3() {
         let input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         box_filter(&input, &mut output, 3);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(1.333333333, output[0]);
@@ -220,7 +220,7 @@ mod tests {
     #[test]
     fn width4() {
         let input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         box_filter(&input, &mut output, 4);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(1.5, output[0]);
@@ -231,7 +231,7 @@ mod tests {
     #[test]
     fn width5() {
         let input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         box_filter(&input, &mut output, 5);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(2.0, output[0]);
@@ -242,7 +242,7 @@ mod tests {
     #[test]
     fn gaussian1() {
         let mut input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         gaussian_filter(&mut input, &mut output, 1.6, 3);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(1.88888889, output[0]);
@@ -253,7 +253,7 @@ mod tests {
     #[test]
     fn gaussian2() {
         let mut input = [1.0, 2.0, 4.0];
-        let mut output = input.clone();
+        let mut output = input;
         gaussian_filter(&mut input, &mut output, 3.6, 4);
         assert_eq!(input.len(), output.len());
         assert_eq_float!(2.3322449, output[0]);
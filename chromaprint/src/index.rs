@@ -0,0 +1,58 @@
+/// Subfingerprint of silence, which is skipped when extracting index keys.
+const SILENCE: u32 = 627964279;
+/// Index of the subfingerprint at which extraction of keys starts.
+const QUERY_START: usize = 80;
+/// Maximal number of extracted keys.
+const QUERY_SIZE: usize = 120;
+/// Number of the most significant bits of subfingerprints kept in keys.
+const QUERY_BITS: u32 = 28;
+
+/// Extracts keys for looking up a fingerprint in an inverted index, like the AcoustID server does.
+///
+/// This follows `acoustid_extract_query` from [pg_acoustid](https://github.com/acoustid/pg_acoustid):
+/// subfingerprints of silence are removed, then up to 120 distinct keys are taken starting
+/// from the 80th remaining subfingerprint (or earlier, if the fingerprint is too short to collect
+/// 120 keys from there). A key is a subfingerprint with its lowest 4 bits cleared.
+/// Keys are returned in the order of their first occurrence.
+pub fn index_keys(fp: &[u32]) -> Vec<u32> {
+    let mask = u32::MAX << (32 - QUERY_BITS);
+    let cleaned = fp.iter().copied().filter(|&sub_fp| sub_fp != SILENCE).collect::<Vec<_>>();
+
+    let start = cleaned.len().saturating_sub(QUERY_SIZE).min(QUERY_START);
+    let mut keys = Vec::with_capacity(QUERY_SIZE);
+    for sub_fp in &cleaned[start..] {
+        if keys.len() == QUERY_SIZE {
+            break;
+        }
+        let key = sub_fp & mask;
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::{index_keys, SILENCE};
+    use crate::utils::pseudo_random;
+
+    #[test]
+    fn small_fingerprint() {
+        assert_eq!(index_keys(&[0x15, 0x12, SILENCE, 0x1F, 0x20, 0x7, 0x20]), [0x10, 0x20, 0x0]);
+        assert!(index_keys(&[SILENCE; 10]).is_empty());
+        assert!(index_keys(&[]).is_empty());
+    }
+
+    #[test]
+    fn long_fingerprint() {
+        let fp = pseudo_random(1, 300);
+        let keys = index_keys(&fp);
+        assert_eq!(keys.len(), 120);
+        assert_eq!(keys[0], fp[80] & !0xF);
+        assert_eq!(keys[119], fp[199] & !0xF);
+
+        // Short fingerprints start earlier to collect as many keys as possible.
+        assert_eq!(index_keys(&fp[..150])[0], fp[30] & !0xF);
+    }
+}
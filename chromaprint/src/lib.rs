@@ -1,21 +1,84 @@
 //! Pure Rust port of [chromaprint](https://acoustid.org/chromaprint)
 
+pub use algorithm::{
+    algorithm_name, fingerprint_duration, subfingerprint_duration, ALGORITHM_DEFAULT, ALGORITHM_TEST1, ALGORITHM_TEST2,
+    ALGORITHM_TEST3, ALGORITHM_TEST4, ALGORITHM_TEST5,
+};
+pub use analysis::{
+    change_rate_windows, fingerprint_entropy, format_subfingerprint, mini_fingerprints, quantize, resize_fingerprint,
+    reverse_bits_u32, trim_constant_tail,
+};
+pub use base64::{
+    decode_fingerprint, decode_fingerprint_streaming, encode_fingerprint, encode_fingerprint_into, DecodeError,
+};
+pub use bit_planes::{
+    bit_frequency, bit_planes_distance, compare_against_planes, from_bit_planes, to_bit_planes, write_bit_planes,
+    BitPlanes, BitPlanesError,
+};
+#[cfg(feature = "ndarray")]
+pub use bit_planes::decompress_to_bits;
+pub use comparison::{
+    acoustid_score, aligned_overlap, bit_agreement, classify_match, compare_checked, compare_compressed,
+    compare_detailed, compare_fingerprints, compare_readers, compare_symmetric, containment_score, edit_distance,
+    fingerprint_distance, fingerprint_distance_inverted, jaccard_similarity, match_coarse_to_fine, match_fft,
+    match_with_drift, matching_pairs, merge_overlapping, rank_matches, CompareError, CompareOptions, DetailedMatch,
+    DriftMatch, MatchScore, MatchSummary, MatchVerdict,
+};
+#[cfg(feature = "rayon")]
+pub use comparison::compare_fingerprints_parallel;
+pub use compression::{
+    compress_concat, compress_fingerprint, compress_fingerprint_24, compress_fingerprint_exact,
+    compress_fingerprint_i32, compress_fingerprint_opts, compress_fingerprint_typed, compress_fingerprint_width,
+    compressed_size, compression_ratio, compression_report, concat_fingerprints, decode_cost, decompress_all,
+    decompress_fingerprint, decompress_fingerprint_24, decompress_fingerprint_consuming, decompress_fingerprint_lossy,
+    decompress_fingerprint_opts, decompress_fingerprint_width, decompress_fingerprint_with,
+    decompress_fingerprint_with_stats, decompress_from_bytes, decompress_into_slice, deltas, estimate_length,
+    fingerprint_spans, is_valid_fingerprint, pack_spans, relabel_algorithm, span_diff, split_regions, CompressError,
+    CompressOptions, CompressionReport, DecodeCost, DecodeStats, DecompressError, FingerprintHeader, SpanDiff,
+};
+pub use fingerprint::{CompressedFingerprint, Fingerprint, FingerprintError};
 pub use fingerprint_matcher::{match_fingerprints, Segment};
 pub use fingerprinter::{Configuration, Fingerprinter};
+#[cfg(feature = "json")]
+pub use fpcalc::{from_fpcalc_json, ParseError};
+pub use index::index_keys;
+pub use query::Query;
+#[cfg(feature = "bytemuck")]
+pub use raw::{decompress_or_borrow, fingerprint_from_bytes};
+pub use raw::{fingerprint_from_be_bytes, fingerprint_from_le_bytes, CastError};
+#[cfg(feature = "testutil")]
+pub use utils::generate_fingerprint;
 
+mod algorithm;
+mod analysis;
 mod audio_processor;
+mod base64;
+mod bit_planes;
 mod chroma;
 mod chroma_filter;
 mod chroma_normalizer;
 mod classifier;
+mod comparison;
+mod compression;
 mod fft;
 mod filter;
+mod fingerprint;
+#[cfg(feature = "serde")]
+pub mod fingerprint_base64;
 mod fingerprint_calculator;
 mod fingerprint_matcher;
 mod fingerprinter;
+#[cfg(feature = "json")]
+mod fpcalc;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 mod gaussian;
 mod gradient;
+mod index;
+pub mod prelude;
 mod quantize;
+mod query;
+mod raw;
 mod rolling_image;
 mod stages;
 mod utils;
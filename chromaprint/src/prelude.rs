@@ -0,0 +1,23 @@
+//! Re-exports of the items needed in a typical workflow.
+//!
+//! ```
+//! use rusty_chromaprint::prelude::*;
+//!
+//! let fp = Fingerprint::new(ALGORITHM_DEFAULT, vec![3086176501, 3077772469, 3077638581]).unwrap();
+//!
+//! let compressed = compress_fingerprint(&fp, fp.algorithm());
+//! assert_eq!(decompress_fingerprint(&compressed), Ok((fp.algorithm(), fp.data().to_vec())));
+//!
+//! let encoded = encode_fingerprint(&fp, fp.algorithm());
+//! assert_eq!(decode_fingerprint(&encoded), Ok((fp.algorithm(), fp.data().to_vec())));
+//!
+//! let score = compare_fingerprints(&fp, &fp[1..], &CompareOptions::default()).unwrap();
+//! assert_eq!(score.offset, 1);
+//! ```
+
+pub use crate::algorithm::ALGORITHM_DEFAULT;
+pub use crate::base64::{decode_fingerprint, encode_fingerprint, DecodeError};
+pub use crate::comparison::{compare_checked, compare_fingerprints, CompareError, CompareOptions, MatchScore};
+pub use crate::compression::{compress_fingerprint, decompress_fingerprint, DecompressError};
+pub use crate::fingerprint::{CompressedFingerprint, Fingerprint, FingerprintError};
+pub use crate::fingerprinter::{Configuration, Fingerprinter};
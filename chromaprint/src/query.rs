@@ -0,0 +1,44 @@
+//! Parameters of AcoustID lookups.
+
+use crate::fingerprint::CompressedFingerprint;
+
+/// Fingerprint of a track together with its duration, as sent to the AcoustID lookup API.
+///
+/// Only the form fields are built, sending them is left to the HTTP client of choice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// Compressed fingerprint of the whole track.
+    pub fingerprint: CompressedFingerprint,
+
+    /// Duration of the track in whole seconds.
+    pub duration: u32,
+}
+
+impl Query {
+    /// Form fields of the lookup, the base64-encoded fingerprint and the duration.
+    pub fn to_query_params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("fingerprint", self.fingerprint.to_base64()),
+            ("duration", self.duration.to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::compress_fingerprint;
+    use crate::fingerprint::CompressedFingerprint;
+    use crate::query::Query;
+
+    #[test]
+    fn query_params() {
+        let query = Query {
+            fingerprint: CompressedFingerprint::new(compress_fingerprint(&[1], 1)),
+            duration: 213,
+        };
+        assert_eq!(query.to_query_params(), [
+            ("fingerprint", "AQAAAQE".to_string()),
+            ("duration", "213".to_string()),
+        ]);
+    }
+}
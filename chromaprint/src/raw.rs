@@ -0,0 +1,141 @@
+//! Conversions of fingerprints stored as raw subfingerprints.
+//!
+//! Chromaprint works with subfingerprints as `u32` values of the host, so compression and
+//! comparison always operate on the logical values. Only bytes of stored fingerprints have
+//! an endianness, which has to be known when reading them.
+
+#[cfg(feature = "bytemuck")]
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "bytemuck")]
+use crate::compression::{decompress_fingerprint, DecompressError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CastError {
+    /// Bytes are not aligned to 4 bytes.
+    Misaligned,
+    /// Number of bytes is not a multiple of 4.
+    InvalidLength,
+}
+
+impl Display for CastError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CastError::Misaligned => write!(f, "Fingerprint data is not aligned"),
+            CastError::InvalidLength => write!(f, "Length of fingerprint data is not a multiple of 4"),
+        }
+    }
+}
+
+impl std::error::Error for CastError {}
+
+/// Reads a fingerprint from bytes of little-endian subfingerprints.
+pub fn fingerprint_from_le_bytes(bytes: &[u8]) -> Result<Vec<u32>, CastError> {
+    read_subfingerprints(bytes, u32::from_le_bytes)
+}
+
+/// Reads a fingerprint from bytes of big-endian subfingerprints.
+pub fn fingerprint_from_be_bytes(bytes: &[u8]) -> Result<Vec<u32>, CastError> {
+    read_subfingerprints(bytes, u32::from_be_bytes)
+}
+
+fn read_subfingerprints(bytes: &[u8], from_bytes: fn([u8; 4]) -> u32) -> Result<Vec<u32>, CastError> {
+    if bytes.len() % 4 != 0 {
+        return Err(CastError::InvalidLength);
+    }
+    Ok(bytes.chunks_exact(4).map(|chunk| from_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect())
+}
+
+/// Reinterprets bytes of native-endian subfingerprints as a fingerprint without copying.
+///
+/// This is useful for comparing fingerprints stored in a memory-mapped file.
+#[cfg(feature = "bytemuck")]
+pub fn fingerprint_from_bytes(bytes: &[u8]) -> Result<&[u32], CastError> {
+    bytemuck::try_cast_slice(bytes).map_err(|e| match e {
+        bytemuck::PodCastError::OutputSliceWouldHaveSlop => CastError::InvalidLength,
+        _ => CastError::Misaligned,
+    })
+}
+
+/// Returns the subfingerprints of either raw or compressed data.
+///
+/// Raw data is borrowed when it is aligned and copied otherwise, compressed data
+/// is decompressed and its algorithm id is dropped. Raw data with a trailing partial
+/// subfingerprint is reported as [DecompressError::UnexpectedEndOfData].
+#[cfg(feature = "bytemuck")]
+pub fn decompress_or_borrow(data: &[u8], raw: bool) -> Result<Cow<'_, [u32]>, DecompressError> {
+    if !raw {
+        return decompress_fingerprint(data).map(|(_, fp)| Cow::Owned(fp));
+    }
+    if data.len() % 4 != 0 {
+        return Err(DecompressError::UnexpectedEndOfData);
+    }
+    match fingerprint_from_bytes(data) {
+        Ok(fp) => Ok(Cow::Borrowed(fp)),
+        // The length is checked above, so the data can only be misaligned.
+        Err(_) => read_subfingerprints(data, u32::from_ne_bytes)
+            .map(Cow::Owned)
+            .map_err(|_| DecompressError::UnexpectedEndOfData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "bytemuck")]
+    use std::borrow::Cow;
+
+    #[cfg(feature = "bytemuck")]
+    use crate::comparison::{compare_fingerprints, CompareOptions};
+    #[cfg(feature = "bytemuck")]
+    use crate::compression::{compress_fingerprint, DecompressError};
+    #[cfg(feature = "bytemuck")]
+    use crate::raw::{decompress_or_borrow, fingerprint_from_bytes};
+    use crate::raw::{fingerprint_from_be_bytes, fingerprint_from_le_bytes, CastError};
+    #[cfg(feature = "bytemuck")]
+    use crate::utils::pseudo_random;
+
+    #[test]
+    fn endianness() {
+        let bytes = [0x01, 0x00, 0x00, 0x80, 0x12, 0x34, 0x56, 0x78];
+        assert_eq!(fingerprint_from_le_bytes(&bytes), Ok(vec![0x8000_0001, 0x7856_3412]));
+        assert_eq!(fingerprint_from_be_bytes(&bytes), Ok(vec![0x0100_0080, 0x1234_5678]));
+        assert_eq!(fingerprint_from_le_bytes(&[]), Ok(vec![]));
+        assert_eq!(fingerprint_from_be_bytes(&bytes[..5]), Err(CastError::InvalidLength));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn cast_bytes() {
+        let fp = [pseudo_random(1, 100), pseudo_random(2, 100)].concat();
+        let bytes: &[u8] = bytemuck::cast_slice(&fp);
+
+        let a = fingerprint_from_bytes(&bytes[..400]).unwrap();
+        let b = fingerprint_from_bytes(&bytes[400..]).unwrap();
+        assert_eq!(a, &fp[..100]);
+        assert_eq!(b, &fp[100..]);
+        assert!(compare_fingerprints(a, b, &CompareOptions::default()).is_some());
+
+        assert_eq!(fingerprint_from_bytes(&bytes[1..5]), Err(CastError::Misaligned));
+        assert_eq!(fingerprint_from_bytes(&bytes[..6]), Err(CastError::InvalidLength));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn raw_or_compressed() {
+        let fp = pseudo_random(1, 100);
+        let raw: &[u8] = bytemuck::cast_slice(&fp);
+        let compressed = compress_fingerprint(&fp, 1);
+
+        let borrowed = decompress_or_borrow(raw, true).unwrap();
+        assert!(matches!(borrowed, Cow::Borrowed(_)));
+        assert_eq!(borrowed, decompress_or_borrow(&compressed, false).unwrap());
+        assert_eq!(borrowed, fp);
+
+        let mut misaligned = vec![0];
+        misaligned.extend_from_slice(raw);
+        assert_eq!(decompress_or_borrow(&misaligned[1..], true).unwrap(), fp);
+        assert_eq!(decompress_or_borrow(&raw[..7], true), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decompress_or_borrow(&misaligned[1..8], true), Err(DecompressError::UnexpectedEndOfData));
+    }
+}
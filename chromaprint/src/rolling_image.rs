@@ -44,8 +44,8 @@ impl RollingIntegralImage {
         assert_eq!(self.columns, row.len());
 
         let mut sum = 0.0;
-        for i in 0..self.columns {
-            sum += row[i].into();
+        for (i, &value) in row.iter().enumerate() {
+            sum += value.into();
             self.row_mut(self.rows)[i] = sum;
         }
 
@@ -68,13 +68,13 @@ impl RollingIntegralImage {
     }
 
     fn row(&self, mut i: usize) -> &[f64] {
-        i = i % self.max_rows;
-        return &self.data[i * self.columns..][..self.columns];
+        i %= self.max_rows;
+        &self.data[i * self.columns..][..self.columns]
     }
 
     fn row_mut(&mut self, mut i: usize) -> &mut [f64] {
-        i = i % self.max_rows;
-        return &mut self.data[i * self.columns..][..self.columns];
+        i %= self.max_rows;
+        &mut self.data[i * self.columns..][..self.columns]
     }
 
     pub(crate) fn reset(&mut self) {
@@ -0,0 +1,37 @@
+//! The decoders that do not allocate are marked with `#[no_panic]` when built with `--cfg no_panic`,
+//! so this test only links if the optimizer proved that none of them can panic on any input.
+//!
+//! Run it with
+//! `RUSTFLAGS="--cfg no_panic" cargo test --profile no-panic -p rusty-chromaprint --test no_panic`.
+//! The extra profile enables fat LTO, without it the proof fails across codegen units.
+//! The allocating decoders like `decompress_fingerprint` are not covered, since allocation itself may panic.
+#![cfg(no_panic)]
+
+use rusty_chromaprint::{
+    compress_fingerprint, decode_cost, decompress_into_slice, estimate_length, is_valid_fingerprint, split_regions,
+    DecompressError,
+};
+
+#[test]
+fn truncated_headers() {
+    let compressed = compress_fingerprint(&[1, 5, 0xffff_ffff, 7, 0], 1);
+    let mut out = [0; 5];
+    assert_eq!(decompress_into_slice(&compressed, &mut out), Ok(1));
+    assert_eq!(out, [1, 5, 0xffff_ffff, 7, 0]);
+
+    for len in 0..4 {
+        let truncated = &compressed[..len];
+        assert_eq!(decompress_into_slice(truncated, &mut out), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(estimate_length(truncated), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(decode_cost(truncated), Err(DecompressError::UnexpectedEndOfData));
+        assert_eq!(split_regions(truncated), Err(DecompressError::UnexpectedEndOfData));
+        assert!(!is_valid_fingerprint(truncated));
+    }
+
+    for len in 4..compressed.len() {
+        let truncated = &compressed[..len];
+        assert!(decompress_into_slice(truncated, &mut out).is_err());
+        assert!(split_regions(truncated).is_err());
+        assert!(!is_valid_fingerprint(truncated));
+    }
+}
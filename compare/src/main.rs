@@ -47,7 +47,7 @@ fn calc_fingerprint(path: impl AsRef<Path>, config: &Configuration) -> anyhow::R
 
     let track_id = track.id;
 
-    let mut printer = Fingerprinter::new(&config);
+    let mut printer = Fingerprinter::new(config);
     let sample_rate = track.codec_params.sample_rate.context("missing sample rate")?;
     let channels = track.codec_params.channels.context("missing audio channels")?.count() as u32;
     printer.start(sample_rate, channels).context("initializing fingerprinter")?;
@@ -55,12 +55,7 @@ fn calc_fingerprint(path: impl AsRef<Path>, config: &Configuration) -> anyhow::R
 
     let mut sample_buf = None;
 
-    loop {
-        let packet = match format.next_packet() {
-            Ok(packet) => packet,
-            Err(_) => break,
-        };
-
+    while let Ok(packet) = format.next_packet() {
         if packet.track_id() != track_id {
             continue;
         }
@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_chromaprint::fuzzing::{round_trips, shrink_failing};
+
+fuzz_target!(
+    init: {
+        // libfuzzer-sys aborts on any panic, which would keep round_trips from catching it
+        // and the input from being minimized, so the default hook is restored.
+        drop(std::panic::take_hook());
+    },
+    |data: &[u8]| {
+        let fp = data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if !round_trips(&fp) {
+            eprintln!("round trip failed, minimal reproducer: {:?}", shrink_failing(&fp));
+            std::process::abort();
+        }
+    }
+);